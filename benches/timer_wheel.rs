@@ -0,0 +1,130 @@
+//! Compares the timer wheel of the executors against the binary heap it
+//! replaced. Run with
+//! `cargo bench --features bench-internals --bench timer_wheel`.
+
+// `is_multiple_of` is only stable since Rust 1.87
+#![allow(clippy::manual_is_multiple_of)]
+
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::Arc,
+    task::{Wake, Waker},
+    time::{Duration, Instant},
+};
+
+use mini_async_runtime::timer::wheel::TimerWheel;
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// The binary heap the wheel replaced, with the same lazy cancellation.
+struct HeapTimers {
+    heap: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+    next_id: u64,
+}
+
+impl HeapTimers {
+    fn insert(&mut self, deadline: Instant, waker: Waker) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.heap.push(Reverse((deadline, id)));
+        self.wakers.insert(id, waker);
+        id
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.wakers.remove(&id);
+        if self.heap.len() > 64 && self.heap.len() > 2 * self.wakers.len() {
+            let wakers = &self.wakers;
+            self.heap.retain(|Reverse((_, id))| wakers.contains_key(id));
+        }
+    }
+
+    fn expire(&mut self, now: Instant) -> Vec<Waker> {
+        let mut expired = Vec::new();
+        while let Some(&Reverse((deadline, id))) = self.heap.peek() {
+            if deadline > now {
+                break;
+            }
+            self.heap.pop();
+            expired.extend(self.wakers.remove(&id));
+        }
+        expired
+    }
+}
+
+/// Arms a timeout per request and cancels most of them before they fire,
+/// while time advances by a millisecond every 100 requests.
+fn run_requests(
+    start: Instant,
+    requests: u64,
+    mut insert: impl FnMut(Instant) -> u64,
+    mut remove: impl FnMut(u64),
+    mut expire: impl FnMut(Instant) -> usize,
+) -> (Duration, usize) {
+    let mut now = start;
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut armed = Vec::new();
+    let mut fired = 0;
+    let began = Instant::now();
+    for request in 0..requests {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        armed.push(insert(now + Duration::from_millis(50 + seed % 5000)));
+        // 95% of the requests complete before their timeout
+        if seed % 20 != 0 {
+            remove(armed.swap_remove(seed as usize % armed.len()));
+        }
+        if request % 100 == 0 {
+            now += Duration::from_millis(1);
+            fired += expire(now);
+        }
+    }
+    (began.elapsed(), fired)
+}
+
+fn main() {
+    const REQUESTS: u64 = 2_000_000;
+    let waker = Waker::from(Arc::new(NoopWaker));
+
+    // Deadlines fall on ticks of the wheel, so both fire the same timers
+    let start = Instant::now();
+    let wheel = RefCell::new(TimerWheel::new(start));
+    let (wheel_time, wheel_fired) = run_requests(
+        start,
+        REQUESTS,
+        |deadline| wheel.borrow_mut().insert(deadline, waker.clone()),
+        |id| wheel.borrow_mut().remove(id),
+        |now| wheel.borrow_mut().expire(now).len(),
+    );
+
+    let heap = RefCell::new(HeapTimers {
+        heap: BinaryHeap::new(),
+        wakers: HashMap::new(),
+        next_id: 0,
+    });
+    let (heap_time, heap_fired) = run_requests(
+        start,
+        REQUESTS,
+        |deadline| heap.borrow_mut().insert(deadline, waker.clone()),
+        |id| heap.borrow_mut().remove(id),
+        |now| heap.borrow_mut().expire(now).len(),
+    );
+
+    assert_eq!(wheel_fired, heap_fired);
+    println!(
+        "{} requests: wheel {:?} ({:.0} ns/request), binary heap {:?} ({:.0} ns/request)",
+        REQUESTS,
+        wheel_time,
+        wheel_time.as_nanos() as f64 / REQUESTS as f64,
+        heap_time,
+        heap_time.as_nanos() as f64 / REQUESTS as f64,
+    );
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+};
+
+use futures_lite::future;
+
+use crate::task::join_handle::{JoinError, JoinHandle};
+
+use super::spawn_local;
+
+/// A group of tasks spawned on the current executor, whose results are
+/// collected in the order the tasks finish.
+///
+/// Every task of the set is awaited with its own waker. When a task finishes,
+/// its waker queues the task as ready, so [`LocalJoinSet::join_next`] never
+/// polls the handles of tasks that are still running.
+///
+/// Dropping the set cancels all of its tasks.
+#[derive(Debug)]
+pub struct LocalJoinSet<T> {
+    tasks: BTreeMap<u64, Entry<T>>,
+    next_id: u64,
+    ready: Arc<ReadyQueue>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    handle: JoinHandle<T>,
+    // Registered as the awaiter of the task, queues `id` when it's woken
+    waker: Waker,
+}
+
+/// Ids of the futures whose wakers were invoked, in the order they were
+/// woken.
+#[derive(Debug, Default)]
+pub(crate) struct ReadyQueue {
+    inner: Mutex<ReadyInner>,
+}
+
+#[derive(Debug, Default)]
+struct ReadyInner {
+    ids: VecDeque<u64>,
+    // The task blocked on `join_next`
+    waiter: Option<Waker>,
+}
+
+#[derive(Debug)]
+struct EntryWaker {
+    id: u64,
+    ready: Arc<ReadyQueue>,
+}
+
+impl ReadyQueue {
+    /// Returns a waker that queues `id` when it's woken.
+    pub(crate) fn waker(self: &Arc<Self>, id: u64) -> Waker {
+        Waker::from(Arc::new(EntryWaker {
+            id,
+            ready: self.clone(),
+        }))
+    }
+
+    /// Pops the next ready id. If there is none, the task of `cx` is woken
+    /// once there is one.
+    pub(crate) fn pop(&self, cx: &mut Context<'_>) -> Option<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.ids.pop_front();
+        if id.is_none() {
+            inner.waiter = Some(cx.waker().clone());
+        }
+        id
+    }
+
+    /// Takes every ready id. The task of `cx` is woken once there are new
+    /// ones.
+    pub(crate) fn take_all(&self, cx: &mut Context<'_>) -> VecDeque<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.waiter = Some(cx.waker().clone());
+        mem::take(&mut inner.ids)
+    }
+}
+
+impl Wake for EntryWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let waiter = {
+            let mut inner = self.ready.inner.lock().unwrap();
+            inner.ids.push_back(self.id);
+            inner.waiter.take()
+        };
+        if let Some(waiter) = waiter {
+            waiter.wake();
+        }
+    }
+}
+
+impl<T: 'static> LocalJoinSet<T> {
+    pub fn new() -> Self {
+        LocalJoinSet {
+            tasks: BTreeMap::new(),
+            next_id: 0,
+            ready: Arc::new(ReadyQueue::default()),
+        }
+    }
+
+    /// Spawns `future` onto the current executor and adds it to the set.
+    pub fn spawn(&mut self, future: impl Future<Output = T> + 'static) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut entry = Entry {
+            handle: spawn_local(future),
+            waker: self.ready.waker(id),
+        };
+        // Polling the handle registers the entry's waker as the awaiter of
+        // the task. It can't be finished yet since it never ran.
+        let _ = entry.poll();
+        self.tasks.insert(id, entry);
+    }
+
+    /// Waits for any task of the set to finish and returns its result, or
+    /// `None` if the set is empty.
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        future::poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    pub fn poll_join_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, JoinError>>> {
+        loop {
+            if self.tasks.is_empty() {
+                return Poll::Ready(None);
+            }
+            let Some(id) = self.ready.pop(cx) else {
+                return Poll::Pending;
+            };
+            // The id may belong to a task that was already joined
+            let Some(entry) = self.tasks.get_mut(&id) else {
+                continue;
+            };
+            if let Poll::Ready(res) = entry.poll() {
+                self.tasks.remove(&id);
+                return Poll::Ready(Some(res));
+            }
+        }
+    }
+
+    /// Cancels every task of the set. They stay in the set, and
+    /// [`LocalJoinSet::join_next`] returns `Err(JoinError::Cancelled)` for
+    /// the ones that didn't finish before.
+    pub fn abort_all(&mut self) {
+        for entry in self.tasks.values() {
+            entry.handle.abort();
+        }
+    }
+
+    /// Returns the number of tasks in the set, including the finished ones
+    /// that weren't joined yet.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+impl<T: 'static> Default for LocalJoinSet<T> {
+    fn default() -> Self {
+        LocalJoinSet::new()
+    }
+}
+
+impl<T> Entry<T> {
+    fn poll(&mut self) -> Poll<Result<T, JoinError>> {
+        let cx = &mut Context::from_waker(&self.waker);
+        Pin::new(&mut self.handle).poll(cx)
+    }
+}
+
+impl<T> Drop for LocalJoinSet<T> {
+    fn drop(&mut self) {
+        for entry in self.tasks.values() {
+            entry.handle.abort();
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{cell::Cell, rc::Rc};
+
+use futures_lite::future;
+
+use crate::executor::yield_now;
+
+use super::{join_set::LocalJoinSet, local_executor::LocalExecutor};
+
+#[test]
+fn join_next_returns_tasks_in_completion_order() {
+    let local_ex = LocalExecutor::default();
+    let order = local_ex.run(async {
+        let mut set = LocalJoinSet::new();
+        for i in (0..4).rev() {
+            set.spawn(async move {
+                for _ in 0..i {
+                    yield_now().await;
+                }
+                i
+            });
+        }
+        assert_eq!(set.len(), 4);
+
+        let mut order = Vec::new();
+        while let Some(res) = set.join_next().await {
+            order.push(res.unwrap());
+        }
+        assert!(set.is_empty());
+        order
+    });
+    assert_eq!(order, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn join_next_on_empty_set() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let mut set = LocalJoinSet::<()>::new();
+        assert!(set.join_next().await.is_none());
+    });
+}
+
+#[test]
+fn join_next_reports_panics() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let mut set = LocalJoinSet::new();
+        set.spawn(async { panic!("task panicked") });
+        let err = set.join_next().await.unwrap().unwrap_err();
+        assert!(err.is_panic());
+    });
+}
+
+#[test]
+fn abort_all_cancels_pending_tasks() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let mut set = LocalJoinSet::new();
+        set.spawn(async { 1 });
+        set.spawn(future::pending());
+        yield_now().await;
+        set.abort_all();
+
+        let mut results: Vec<_> = Vec::new();
+        while let Some(res) = set.join_next().await {
+            results.push(res.map_err(|err| err.is_cancelled()));
+        }
+        results.sort();
+        assert_eq!(results, vec![Ok(1), Err(true)]);
+    });
+}
+
+#[test]
+fn dropping_the_set_cancels_its_tasks() {
+    let local_ex = LocalExecutor::default();
+    let ran = Rc::new(Cell::new(false));
+    let task_ran = ran.clone();
+    local_ex.run(async move {
+        let mut set = LocalJoinSet::new();
+        set.spawn(async move {
+            yield_now().await;
+            task_ran.set(true);
+        });
+        drop(set);
+        for _ in 0..3 {
+            yield_now().await;
+        }
+    });
+    assert!(!ran.get());
+}
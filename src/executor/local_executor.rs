@@ -1,42 +1,116 @@
 use futures_lite::pin;
 use std::{
-    cell::RefCell,
+    any::Any,
+    cell::{Cell, RefCell},
+    fmt,
     future::Future,
+    io,
     rc::Rc,
+    sync::Arc,
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    time::{Duration, Instant},
 };
 
-use crate::{executor::LOCAL_EX, parking, reactor::Reactor, task::join_handle::JoinHandle};
+use crate::{
+    executor::LOCAL_EX,
+    parking,
+    reactor::Reactor,
+    task::{join_handle::JoinHandle, live_tasks::LiveTasks},
+};
 
 use super::{
     local_executor_builder::LocalExecutorBuilder,
     placement::Placement,
     queue_manager::QueueManager,
-    task_queue::{TaskQueue, TaskQueueHandle},
+    registry,
+    task_dump::{self, TaskDump},
+    task_queue::{Latency, TaskQueue, TaskQueueHandle, DEFAULT_SHARES, MAX_SHARES},
+    task_queue_stats::TaskQueueStats,
 };
 
 #[derive(Debug)]
-pub(crate) struct LocalExecutor {
+pub struct LocalExecutor {
     pub(crate) id: usize,
+    name: String,
     pub(crate) queues: Rc<RefCell<QueueManager>>,
     reactor: Rc<Reactor>,
     parker: parking::Parker,
+    // Longest time tasks may run before going back to polling I/O if no
+    // latency sensitive queue asks for less
+    preempt_timer_duration: Duration,
+    // Set by `yield_now` so that the executor goes back to polling I/O
+    // before running more tasks
+    yield_requested: Cell<bool>,
+    // How long to keep polling the ring for new events once there is no task
+    // to run, before going to sleep in it
+    spin_before_park: Duration,
+    panic_hook: Option<PanicHook>,
+    live_tasks: Rc<LiveTasks>,
+    // Last dump requested by a signal that this executor printed
+    pub(crate) dump_generation: Cell<u64>,
 }
 
 pub(crate) const DEFAULT_RING_SUBMISSION_DEPTH: usize = 128;
 
+pub(crate) const DEFAULT_PREEMPT_TIMER: Duration = Duration::from_millis(100);
+
+/// Settings of a [`LocalExecutor`], as picked by its builder.
+#[derive(Debug, Clone)]
+pub(crate) struct ExecutorConfig {
+    pub(crate) name: String,
+    pub(crate) ring_depth: usize,
+    pub(crate) preempt_timer: Duration,
+    pub(crate) spin_before_park: Duration,
+    pub(crate) panic_hook: Option<PanicHook>,
+}
+
+/// Called on the executor's thread with the payload of every task that
+/// panics.
+#[derive(Clone)]
+pub(crate) struct PanicHook(pub(crate) Arc<PanicHookFn>);
+
+type PanicHookFn = dyn Fn(&(dyn Any + Send)) + Send + Sync;
+
+impl fmt::Debug for PanicHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PanicHook")
+    }
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        ExecutorConfig {
+            name: String::from("unnamed"),
+            ring_depth: DEFAULT_RING_SUBMISSION_DEPTH,
+            preempt_timer: DEFAULT_PREEMPT_TIMER,
+            spin_before_park: Duration::ZERO,
+            panic_hook: None,
+        }
+    }
+}
+
 impl LocalExecutor {
-    pub fn new(cpu_binding: Option<impl IntoIterator<Item = usize>>) -> Self {
-        match cpu_binding {
-            Some(cpu_set) => bind_to_cpu_set(cpu_set),
-            None => {}
+    pub(crate) fn new(
+        cpu_binding: Option<impl IntoIterator<Item = usize>>,
+        config: ExecutorConfig,
+    ) -> io::Result<Self> {
+        if let Some(cpu_set) = cpu_binding {
+            bind_to_cpu_set(cpu_set)?;
         }
-        LocalExecutor {
-            id: 0,
+        let reactor = Rc::new(Reactor::new(config.ring_depth)?);
+        Ok(LocalExecutor {
+            id: registry::register(reactor.sys.notifier()),
+            name: config.name,
             queues: Rc::new(RefCell::new(QueueManager::new())),
             parker: parking::Parker::new(),
-            reactor: Rc::new(Reactor::new(DEFAULT_RING_SUBMISSION_DEPTH)),
-        }
+            reactor,
+            preempt_timer_duration: config.preempt_timer,
+            yield_requested: Cell::new(false),
+            spin_before_park: config.spin_before_park,
+            panic_hook: config.panic_hook,
+            live_tasks: Rc::new(LiveTasks::default()),
+            dump_generation: Cell::new(task_dump::dump_generation()),
+        })
     }
 
     pub fn init(&mut self) {
@@ -44,24 +118,68 @@ impl LocalExecutor {
     }
     pub fn default() -> Self {
         let builder = LocalExecutorBuilder::new(Placement::Unbound);
-        builder.build()
+        builder
+            .build()
+            .expect("Failed to build the default LocalExecutor")
     }
 
-    pub fn get_reactor(&self) -> Rc<Reactor> {
+    pub(crate) fn get_reactor(&self) -> Rc<Reactor> {
         self.reactor.clone()
     }
 
     pub fn add_default_task_queue(&self) {
-        self.queues
-            .borrow_mut()
+        self.create_task_queue("default", DEFAULT_SHARES, Latency::NotImportant);
+    }
+
+    /// Creates a new `TaskQueue` that tasks can be spawned into with
+    /// [`LocalExecutor::spawn_into`]. The `shares` determine how much CPU time
+    /// the queue gets relative to the other queues of this executor.
+    ///
+    /// Panics if `shares` is 0 or more than `1 << 22`, or if `latency` is
+    /// `Latency::Matters(Duration::ZERO)`.
+    pub fn create_task_queue(
+        &self,
+        name: &str,
+        shares: usize,
+        latency: Latency,
+    ) -> TaskQueueHandle {
+        assert!(shares > 0, "A TaskQueue needs at least one share");
+        assert!(
+            shares <= MAX_SHARES,
+            "A TaskQueue can't have more than {} shares",
+            MAX_SHARES
+        );
+        assert!(
+            latency != Latency::Matters(Duration::ZERO),
+            "A latency sensitive TaskQueue needs a latency above zero"
+        );
+        let mut queues = self.queues.borrow_mut();
+        let index = queues.next_queue_index;
+        queues.next_queue_index += 1;
+        queues
             .available_queues
-            .insert(0, TaskQueue::new("default"));
+            .insert(index, TaskQueue::new(index, name, shares, latency));
+        TaskQueueHandle { index }
+    }
+
+    /// Returns the handle of the `TaskQueue` that is currently being run,
+    /// or `None` if no task is executing.
+    pub fn current_task_queue(&self) -> Option<TaskQueueHandle> {
+        self.queues
+            .borrow()
+            .active_executing
+            .as_ref()
+            .map(|tq| tq.borrow().handle())
     }
 
     pub fn get_id(&self) -> usize {
         self.id
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     fn get_default_queue(&self) -> Option<Rc<RefCell<TaskQueue>>> {
         self.get_queue(TaskQueueHandle { index: 0 })
     }
@@ -75,13 +193,71 @@ impl LocalExecutor {
     }
 
     pub(crate) fn spawn<T>(&self, future: impl Future<Output = T>) -> JoinHandle<T> {
-        let active_executing = self.queues.borrow().active_executing.clone();
-        let tq = active_executing
-            .clone() // this clone is cheap because we clone an `Option<Rc<_>>`
-            .or_else(|| self.get_default_queue())
-            .unwrap();
+        self.spawn_named(None, None, future)
+    }
+
+    /// Spawns a task with an optional name onto the `TaskQueue` identified by
+    /// `handle`, or the current one if `None`.
+    pub(crate) fn spawn_named<T>(
+        &self,
+        name: Option<String>,
+        handle: Option<TaskQueueHandle>,
+        future: impl Future<Output = T>,
+    ) -> JoinHandle<T> {
+        let tq = match handle {
+            Some(handle) => self
+                .get_queue(handle)
+                .unwrap_or_else(|| panic!("No TaskQueue found for {:?}", handle)),
+            None => {
+                let active_executing = self.queues.borrow().active_executing.clone();
+                active_executing
+                    .clone() // this clone is cheap because we clone an `Option<Rc<_>>`
+                    .or_else(|| self.get_default_queue())
+                    .unwrap()
+            }
+        };
         let tq_executor = tq.borrow().ex.clone();
-        tq_executor.spawn_and_schedule(self.id, tq, future)
+        tq_executor.spawn_and_schedule(
+            self.id,
+            self.reactor.sys.notifier().clone(),
+            name,
+            self.live_tasks.clone(),
+            tq,
+            future,
+        )
+    }
+
+    /// Returns the counters of the `TaskQueue` identified by `handle`, or
+    /// `None` if it doesn't belong to this executor.
+    pub fn task_queue_stats(&self, handle: TaskQueueHandle) -> Option<TaskQueueStats> {
+        self.get_queue(handle).map(|tq| tq.borrow().stats.clone())
+    }
+
+    /// Returns the state of every task of this executor that wasn't
+    /// destroyed yet, including the ones that completed but whose
+    /// `JoinHandle` is still alive.
+    pub fn dump_tasks(&self) -> TaskDump {
+        TaskDump {
+            executor_id: self.id,
+            executor_name: self.name.clone(),
+            tasks: self.live_tasks.snapshot(),
+        }
+    }
+
+    /// Returns the number of tasks of this executor that weren't destroyed
+    /// yet.
+    pub fn live_tasks(&self) -> usize {
+        self.live_tasks.len()
+    }
+
+    /// Prints the tasks of this executor if a dump was requested by a signal
+    /// since it last did.
+    fn dump_tasks_if_requested(&self) {
+        let generation = task_dump::dump_generation();
+        if generation != self.dump_generation.get() {
+            self.dump_generation.set(generation);
+            eprintln!("{}", self.dump_tasks());
+        }
     }
 
     /// Runs the executor until the given future completes.
@@ -95,38 +271,87 @@ impl LocalExecutor {
             let cx = &mut Context::from_waker(&waker);
             let join_handle = self.spawn(async move { future.await });
             pin!(join_handle);
+            // Time until which the executor keeps polling the ring instead of
+            // parking, set when it runs out of tasks
+            let mut spin_until = None;
             loop {
                 if let Poll::Ready(t) = join_handle.as_mut().poll(cx) {
-                    // can't be canceled, and join handle is None only upon
-                    // cancellation or panic. So in case of panic this just propagates
-                    return t.unwrap();
+                    // can't be canceled, so this only fails if the future
+                    // panicked. The panic was caught by the task, so raise
+                    // it again with its original payload.
+                    return t.unwrap_or_else(|err| err.resume_unwind());
                 }
 
-                self.parker
-                    .poll_io()
-                    .expect("Failed to poll io! This is actually pretty bad!");
+                let (idle, preempt_timer) = {
+                    let queues = self.queues.borrow();
+                    (
+                        queues.active_queues.is_empty(),
+                        queues.preempt_timer_duration(self.preempt_timer_duration),
+                    )
+                };
+                if idle {
+                    let spin_until =
+                        *spin_until.get_or_insert_with(|| Instant::now() + self.spin_before_park);
+                    if Instant::now() < spin_until {
+                        self.parker
+                            .poll_io(Duration::ZERO)
+                            .expect("Failed to poll io! This is actually pretty bad!");
+                    } else {
+                        // No task can make progress until an event completes
+                        self.parker
+                            .park(None)
+                            .expect("Failed to park! This is actually pretty bad!");
+                    }
+                } else {
+                    self.parker
+                        .poll_io(preempt_timer)
+                        .expect("Failed to poll io! This is actually pretty bad!");
+                }
+                self.reactor.sys.notifier().process_remote_wakes();
+                self.dump_tasks_if_requested();
 
-                // TODO: I/O work
-                self.run_task_queues();
+                if self.run_task_queues() {
+                    spin_until = None;
+                }
             }
         })
     }
 
-    pub(crate) fn spawn_into<T>(
+    /// Spawns a task onto the `TaskQueue` identified by `handle`.
+    ///
+    /// Panics if the `TaskQueue` doesn't belong to this executor.
+    pub fn spawn_into<T>(
         &self,
         future: impl Future<Output = T>,
         handle: TaskQueueHandle,
     ) -> JoinHandle<T> {
-        todo!()
+        self.spawn_named(None, Some(handle), future)
+    }
+
+    /// Returns true if the task currently running should yield the CPU,
+    /// either because the preemption timer installed into the ring fired or
+    /// because it asked to yield.
+    pub fn need_preempt(&self) -> bool {
+        self.yield_requested.get() || self.reactor.need_preempt()
+    }
+
+    pub(crate) fn report_task_panic(&self, payload: &(dyn Any + Send)) {
+        if let Some(PanicHook(hook)) = &self.panic_hook {
+            hook(payload);
+        }
+    }
+
+    pub(crate) fn request_yield(&self) {
+        self.yield_requested.set(true);
     }
 
     fn run_task_queues(&self) -> bool {
         let mut ran = false;
-        loop {
-            // TODO: Check if prempt
+        self.yield_requested.set(false);
+        while !self.need_preempt() {
             if !self.run_one_task_queue() {
                 println!("run_task_queues: no task executed, returning");
-                return false;
+                return ran;
             } else {
                 println!("run_task_queues: Ran is true, loop again");
                 ran = true;
@@ -146,23 +371,38 @@ impl LocalExecutor {
             Some(tq) => {
                 q_manager.active_executing = Some(tq.clone());
                 drop(q_manager);
-                loop {
-                    // TODO: Break if pre-empted or yielded
+                tq.borrow_mut().mark_picked();
+                let start = Instant::now();
+                let mut tasks_run = 0;
+                while !self.need_preempt() {
                     let tq = tq.borrow_mut();
 
                     if let Some(task) = tq.get_task() {
                         drop(tq);
+                        tasks_run += 1;
                         task.run();
                     } else {
                         println!("No task. Break!");
                         break;
                     }
                 }
-                let mut tq_ref = tq.borrow_mut();
-                tq_ref.reset_active();
-                let need_repush = tq_ref.is_active();
+                let (need_repush, vruntime) = {
+                    let mut tq_ref = tq.borrow_mut();
+                    let runtime = start.elapsed();
+                    tq_ref.stats.runtime += runtime;
+                    tq_ref.stats.tasks_run += tasks_run;
+                    tq_ref.reset_active();
+                    if tq_ref.is_active() {
+                        // Still has tasks, so it waits for its next turn
+                        tq_ref.mark_runnable(false);
+                    }
+                    (tq_ref.is_active(), tq_ref.account_vruntime(runtime))
+                };
+                let mut q_manager = self.queues.borrow_mut();
+                q_manager.active_executing = None;
+                q_manager.update_last_vruntime(vruntime);
                 if need_repush {
-                    self.queues.borrow_mut().active_queues.push(tq.clone());
+                    q_manager.active_queues.push(tq);
                 }
                 true
             }
@@ -174,13 +414,21 @@ impl LocalExecutor {
     }
 }
 
-pub(crate) fn bind_to_cpu_set(cpus: impl IntoIterator<Item = usize>) {
+impl Drop for LocalExecutor {
+    fn drop(&mut self) {
+        registry::unregister(self.id);
+        self.reactor.sys.notifier().close();
+    }
+}
+
+pub(crate) fn bind_to_cpu_set(cpus: impl IntoIterator<Item = usize>) -> io::Result<()> {
     let mut cpuset = nix::sched::CpuSet::new();
     for cpu in cpus {
-        cpuset.set(cpu).unwrap();
+        cpuset.set(cpu)?;
     }
     let pid = nix::unistd::Pid::from_raw(0);
-    nix::sched::sched_setaffinity(pid, &cpuset).unwrap();
+    nix::sched::sched_setaffinity(pid, &cpuset)?;
+    Ok(())
 }
 
 pub(crate) fn dummy_waker() -> Waker {
@@ -1,23 +1,220 @@
-use std::path::Iter;
+use std::{
+    any::Any,
+    fmt,
+    future::Future,
+    io,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
 
-use super::{local_executor::LocalExecutor, placement::Placement};
+use super::{
+    local_executor::{ExecutorConfig, LocalExecutor, PanicHook},
+    placement::Placement,
+};
 
-pub(crate) struct LocalExecutorBuilder {
+/// Largest ring depth supported by io_uring.
+const MAX_RING_DEPTH: usize = 32768;
+
+/// Builds a [`LocalExecutor`] with a custom configuration.
+#[derive(Debug)]
+pub struct LocalExecutorBuilder {
     placement: Placement,
+    config: ExecutorConfig,
+    // Only used when the executor gets its own thread
+    stack_size: Option<usize>,
 }
 
 impl LocalExecutorBuilder {
     pub fn new(placement: Placement) -> LocalExecutorBuilder {
-        LocalExecutorBuilder { placement }
+        LocalExecutorBuilder {
+            placement,
+            config: ExecutorConfig::default(),
+            stack_size: None,
+        }
+    }
+
+    /// Names the executor. This is also the name of its thread when it gets
+    /// one.
+    pub fn name(mut self, name: &str) -> Self {
+        self.config.name = name.into();
+        self
+    }
+
+    /// Sets the number of entries of the submission queue of the main ring.
+    pub fn ring_depth(mut self, ring_depth: usize) -> Self {
+        self.config.ring_depth = ring_depth;
+        self
+    }
+
+    /// Sets the longest time tasks may run before the executor goes back to
+    /// polling I/O, unless a latency sensitive queue asks for less.
+    pub fn preempt_timer(mut self, duration: Duration) -> Self {
+        self.config.preempt_timer = duration;
+        self
+    }
+
+    /// Sets how long the executor keeps polling the ring for events once it
+    /// runs out of tasks, before it sleeps in the ring. Spinning trades CPU
+    /// time for lower wake up latency.
+    pub fn spin_before_park(mut self, duration: Duration) -> Self {
+        self.config.spin_before_park = duration;
+        self
+    }
+
+    /// Sets a hook that is called with the payload of every task of the
+    /// executor that panics. The panic is caught either way, and also
+    /// reported to the task's `JoinHandle`.
+    ///
+    /// The hook runs on the executor's thread. If it panics, so does the
+    /// executor.
+    pub fn on_task_panic<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.config.panic_hook = Some(PanicHook(Arc::new(hook)));
+        self
+    }
+
+    /// Sets the stack size of the thread the executor runs on when it gets
+    /// one.
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = Some(stack_size);
+        self
     }
 
-    pub fn build(self) -> LocalExecutor {
-        let cpu_binding = match self.placement {
-            Placement::Unbound => None::<Vec<usize>>,
-            Placement::Fixed(cpu) => Some(vec![cpu]),
-        };
-        let mut ex = LocalExecutor::new(cpu_binding);
+    pub fn build(self) -> Result<LocalExecutor, BuilderError> {
+        validate(&self.config, self.stack_size)?;
+        let cpu_binding = self.cpu_binding()?;
+        let mut ex = LocalExecutor::new(cpu_binding, self.config).map_err(BuilderError::Io)?;
         ex.init();
-        ex
+        Ok(ex)
+    }
+
+    /// Spawns a thread named after the executor, builds the executor on it
+    /// and runs the future created by `fut_factory` to completion.
+    ///
+    /// `fut_factory` is called on the new thread, so the future itself
+    /// doesn't need to be `Send`. The configuration is checked before the
+    /// thread is spawned, while failing to create the executor on the new
+    /// thread panics it and is reported by [`ExecutorJoinHandle::join`].
+    pub fn spawn<F, Fut, T>(self, fut_factory: F) -> Result<ExecutorJoinHandle<T>, BuilderError>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = T> + 'static,
+        T: Send + 'static,
+    {
+        validate(&self.config, self.stack_size)?;
+        let cpu_binding = self.cpu_binding()?;
+        let mut builder = thread::Builder::new().name(self.config.name.clone());
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        let config = self.config;
+        let handle = builder
+            .spawn(move || {
+                let mut ex = LocalExecutor::new(cpu_binding, config)
+                    .expect("Failed to create the LocalExecutor");
+                ex.init();
+                ex.run(fut_factory())
+            })
+            .map_err(BuilderError::Io)?;
+        Ok(ExecutorJoinHandle { handle })
+    }
+
+    fn cpu_binding(&self) -> Result<Option<Vec<usize>>, BuilderError> {
+        let mut bindings = self
+            .placement
+            .cpu_bindings(1)
+            .map_err(BuilderError::Placement)?;
+        Ok(bindings.pop().unwrap())
+    }
+}
+
+/// Checks the settings shared by the executor and pool builders.
+pub(super) fn validate(
+    config: &ExecutorConfig,
+    stack_size: Option<usize>,
+) -> Result<(), BuilderError> {
+    if !(1..=MAX_RING_DEPTH).contains(&config.ring_depth) {
+        return Err(BuilderError::InvalidRingDepth(config.ring_depth));
+    }
+    if config.preempt_timer.is_zero() {
+        return Err(BuilderError::InvalidPreemptTimer(config.preempt_timer));
+    }
+    if config.name.contains('\0') {
+        return Err(BuilderError::InvalidName(config.name.clone()));
+    }
+    if stack_size == Some(0) {
+        return Err(BuilderError::InvalidStackSize(0));
+    }
+    Ok(())
+}
+
+/// Handle to the thread of an executor spawned by
+/// [`LocalExecutorBuilder::spawn`].
+#[derive(Debug)]
+pub struct ExecutorJoinHandle<T> {
+    handle: JoinHandle<T>,
+}
+
+impl<T> ExecutorJoinHandle<T> {
+    pub fn thread(&self) -> &thread::Thread {
+        self.handle.thread()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the executor to finish. Returns the output of its future,
+    /// or an `Err` with the panic payload if the executor panicked.
+    pub fn join(self) -> thread::Result<T> {
+        self.handle.join()
+    }
+}
+
+/// Error returned when a [`LocalExecutor`] can't be built.
+#[derive(Debug)]
+pub enum BuilderError {
+    /// The ring depth must be between 1 and 32768.
+    InvalidRingDepth(usize),
+    /// The preemption timer can't be zero.
+    InvalidPreemptTimer(Duration),
+    /// Names can't contain a nul byte.
+    InvalidName(String),
+    /// The stack size can't be zero.
+    InvalidStackSize(usize),
+    /// The executor couldn't be placed according to its [`Placement`].
+    Placement(io::Error),
+    /// Creating the executor failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::InvalidRingDepth(depth) => write!(
+                f,
+                "Invalid ring depth {}, must be between 1 and {}",
+                depth, MAX_RING_DEPTH
+            ),
+            BuilderError::InvalidPreemptTimer(duration) => {
+                write!(f, "Invalid preemption timer {:?}", duration)
+            }
+            BuilderError::InvalidName(name) => write!(f, "Invalid executor name {:?}", name),
+            BuilderError::InvalidStackSize(size) => write!(f, "Invalid stack size {}", size),
+            BuilderError::Placement(err) => write!(f, "Failed to place the executor: {}", err),
+            BuilderError::Io(err) => write!(f, "Failed to create the executor: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BuilderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuilderError::Placement(err) | BuilderError::Io(err) => Some(err),
+            _ => None,
+        }
     }
 }
@@ -0,0 +1,168 @@
+use std::{
+    any::Any,
+    future::Future,
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{
+    local_executor::{ExecutorConfig, LocalExecutor, PanicHook},
+    local_executor_builder::{self, BuilderError},
+    placement::Placement,
+};
+
+/// Builds a pool of [`LocalExecutor`]s, each running on its own thread.
+///
+/// The `Placement` decides which CPUs every thread of the pool is bound to.
+/// Every executor of the pool gets the same configuration.
+#[derive(Debug)]
+pub struct LocalExecutorPoolBuilder {
+    nr_executors: usize,
+    placement: Placement,
+    config: ExecutorConfig,
+    stack_size: Option<usize>,
+}
+
+impl LocalExecutorPoolBuilder {
+    pub fn new(nr_executors: usize, placement: Placement) -> LocalExecutorPoolBuilder {
+        LocalExecutorPoolBuilder {
+            nr_executors,
+            placement,
+            config: ExecutorConfig {
+                name: String::from("executor"),
+                ..ExecutorConfig::default()
+            },
+            stack_size: None,
+        }
+    }
+
+    /// Sets the prefix of the names of the executors and their threads. The
+    /// executors are named `{prefix}-{index}`.
+    pub fn name(mut self, prefix: &str) -> Self {
+        self.config.name = prefix.into();
+        self
+    }
+
+    /// Sets the number of entries of the submission queue of the main ring
+    /// of every executor.
+    pub fn ring_depth(mut self, ring_depth: usize) -> Self {
+        self.config.ring_depth = ring_depth;
+        self
+    }
+
+    /// Sets the longest time tasks may run before their executor goes back
+    /// to polling I/O, unless a latency sensitive queue asks for less.
+    pub fn preempt_timer(mut self, duration: Duration) -> Self {
+        self.config.preempt_timer = duration;
+        self
+    }
+
+    /// Sets how long every executor keeps polling its ring for events once
+    /// it runs out of tasks, before it sleeps in the ring.
+    pub fn spin_before_park(mut self, duration: Duration) -> Self {
+        self.config.spin_before_park = duration;
+        self
+    }
+
+    /// Sets a hook that is called with the payload of every task of the pool
+    /// that panics, on the thread of the task's executor.
+    pub fn on_task_panic<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.config.panic_hook = Some(PanicHook(Arc::new(hook)));
+        self
+    }
+
+    /// Sets the stack size of the threads of the pool.
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Spawns one thread per executor and runs the future created by
+    /// `fut_factory` on each of them.
+    ///
+    /// `fut_factory` is cloned once per thread and called on that thread, so
+    /// the futures themselves don't need to be `Send`. The futures only
+    /// start once every thread was spawned: if spawning one fails, the
+    /// threads already spawned exit without running anything.
+    pub fn on_all_shards<F, Fut, T>(
+        self,
+        fut_factory: F,
+    ) -> Result<PoolThreadHandles<T>, BuilderError>
+    where
+        F: FnOnce() -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = T> + 'static,
+        T: Send + 'static,
+    {
+        local_executor_builder::validate(&self.config, self.stack_size)?;
+        let cpu_bindings = self
+            .placement
+            .cpu_bindings(self.nr_executors)
+            .map_err(BuilderError::Placement)?;
+
+        let mut handles = Vec::with_capacity(self.nr_executors);
+        let mut starters = Vec::with_capacity(self.nr_executors);
+        for (index, cpu_binding) in cpu_bindings.into_iter().enumerate() {
+            let fut_factory = fut_factory.clone();
+            let name = format!("{}-{}", self.config.name, index);
+            let config = ExecutorConfig {
+                name: name.clone(),
+                ..self.config.clone()
+            };
+            let (starter, start) = mpsc::channel::<()>();
+            let mut builder = thread::Builder::new().name(name);
+            if let Some(stack_size) = self.stack_size {
+                builder = builder.stack_size(stack_size);
+            }
+            let spawned = builder.spawn(move || {
+                // The sender is dropped without sending if the pool failed to
+                // start
+                start.recv().ok()?;
+                let mut ex = LocalExecutor::new(cpu_binding, config)
+                    .expect("Failed to create the LocalExecutor");
+                ex.init();
+                Some(ex.run(fut_factory()))
+            });
+            match spawned {
+                Ok(handle) => {
+                    handles.push(handle);
+                    starters.push(starter);
+                }
+                Err(err) => {
+                    drop(starters);
+                    for handle in handles {
+                        let _ = handle.join();
+                    }
+                    return Err(BuilderError::Io(err));
+                }
+            }
+        }
+        for starter in starters {
+            // The thread can't be gone before it was started
+            starter.send(()).unwrap();
+        }
+        Ok(PoolThreadHandles { handles })
+    }
+}
+
+/// Handles to the threads of a pool spawned by
+/// [`LocalExecutorPoolBuilder::on_all_shards`].
+#[derive(Debug)]
+pub struct PoolThreadHandles<T> {
+    handles: Vec<JoinHandle<Option<T>>>,
+}
+
+impl<T> PoolThreadHandles<T> {
+    /// Waits for every executor of the pool to finish. The result of each
+    /// executor is in the same order as the threads were spawned, and is an
+    /// `Err` with the panic payload if the executor panicked.
+    pub fn join_all(self) -> Vec<thread::Result<T>> {
+        self.handles
+            .into_iter()
+            .map(|handle| handle.join().map(|res| res.expect("The pool was started")))
+            .collect()
+    }
+}
@@ -1,8 +1,33 @@
-use crate::executor::spawn_local;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    task::{Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_lite::future;
+use nix::sys::signal::{self, Signal};
+
+use crate::{
+    executor::{
+        executor, spawn_local, spawn_local_into, spawn_named, task_builder::TaskBuilder,
+        yield_if_needed, yield_now, LOCAL_EX,
+    },
+    task::{join_handle::JoinError, task_id::current_task_id},
+};
 
 use super::{
-    local_executor::LocalExecutor, local_executor_builder::LocalExecutorBuilder,
+    local_executor::LocalExecutor,
+    local_executor_builder::{BuilderError, LocalExecutorBuilder},
+    local_executor_pool_builder::LocalExecutorPoolBuilder,
     placement::Placement,
+    registry, task_dump,
+    task_queue::Latency,
 };
 
 #[test]
@@ -26,10 +51,638 @@ fn simple_spawn() {
 fn local_executor_builder_placement() {
     // The LocalExecutor will now only run on Cpu 0
     let builder = LocalExecutorBuilder::new(Placement::Fixed(0));
-    let local_ex = builder.build();
+    let local_ex = builder.build().unwrap();
     let res = local_ex.run(async {
         let handle = spawn_local(async { 1 + 5 });
         handle.await.unwrap() + 7
     });
     assert_eq!(res, 13)
 }
+
+#[test]
+fn spawn_into_task_queues() {
+    let local_ex = LocalExecutor::default();
+    let res = local_ex.run(async {
+        let foreground = executor().create_task_queue("foreground", 1000, Latency::NotImportant);
+        let background = executor().create_task_queue("background", 100, Latency::NotImportant);
+        let first = spawn_local_into(
+            async move {
+                assert_eq!(executor().current_task_queue(), foreground);
+                1
+            },
+            foreground,
+        );
+        let second = spawn_local_into(
+            async move {
+                assert_eq!(executor().current_task_queue(), background);
+                // Tasks spawned from within a queue land in the same queue
+                spawn_local(async move { executor().current_task_queue() == background })
+                    .await
+                    .unwrap()
+            },
+            background,
+        );
+        first.await.unwrap() + second.await.unwrap() as usize
+    });
+    assert_eq!(res, 2)
+}
+
+#[test]
+fn yield_now_lets_other_tasks_run() {
+    let local_ex = LocalExecutor::default();
+    let order = local_ex.run(async {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let first = spawn_local({
+            let order = order.clone();
+            async move {
+                order.borrow_mut().push(1);
+                yield_now().await;
+                order.borrow_mut().push(3);
+            }
+        });
+        let second = spawn_local({
+            let order = order.clone();
+            async move {
+                order.borrow_mut().push(2);
+            }
+        });
+        first.await.unwrap();
+        second.await.unwrap();
+        order.take()
+    });
+    assert_eq!(order, vec![1, 2, 3])
+}
+
+#[test]
+fn yield_now_alternates_task_queues() {
+    let local_ex = LocalExecutor::default();
+    let order = local_ex.run(async {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let handles: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|name| {
+                let tq = executor().create_task_queue(name, 1000, Latency::NotImportant);
+                let order = order.clone();
+                spawn_local_into(
+                    async move {
+                        for _ in 0..3 {
+                            order.borrow_mut().push(name);
+                            yield_now().await;
+                        }
+                    },
+                    tq,
+                )
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        order.take()
+    });
+    assert_eq!(order.len(), 6);
+    // Both queues start with the same vruntime, so the one that didn't run
+    // first must run next.
+    assert_ne!(order[0], order[1]);
+}
+
+#[test]
+fn need_preempt_once_the_preempt_timer_fires() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let latency = Duration::from_millis(5);
+        let tq = executor().create_task_queue("latency", 1000, Latency::Matters(latency));
+        spawn_local_into(
+            async move {
+                let start = Instant::now();
+                while !executor().need_preempt() {}
+                assert!(start.elapsed() < Duration::from_secs(1));
+                yield_if_needed().await;
+                assert!(!executor().need_preempt());
+            },
+            tq,
+        )
+        .await
+        .unwrap();
+    });
+}
+
+#[test]
+fn latency_queue_runs_while_throughput_queue_is_busy() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let busy_done = Rc::new(Cell::new(false));
+        let throughput = executor().create_task_queue("throughput", 1000, Latency::NotImportant);
+        let latency = executor().create_task_queue(
+            "latency",
+            1000,
+            Latency::Matters(Duration::from_millis(1)),
+        );
+        let busy = spawn_local_into(
+            {
+                let busy_done = busy_done.clone();
+                async move {
+                    let start = Instant::now();
+                    while start.elapsed() < Duration::from_millis(50) {
+                        yield_if_needed().await;
+                    }
+                    busy_done.set(true);
+                }
+            },
+            throughput,
+        );
+        let latency_sensitive = spawn_local_into(
+            {
+                let busy_done = busy_done.clone();
+                async move { busy_done.get() }
+            },
+            latency,
+        );
+        // Go back to the executor so that the next preemption timer accounts
+        // for the now active latency sensitive queue
+        yield_now().await;
+        assert!(!latency_sensitive.await.unwrap());
+        busy.await.unwrap();
+    });
+}
+
+#[test]
+fn wake_from_another_thread() {
+    let local_ex = LocalExecutor::default();
+    let res = local_ex.run(async {
+        let (sender, receiver) = mpsc::channel::<Waker>();
+        let helper = thread::spawn(move || {
+            for waker in receiver {
+                thread::sleep(Duration::from_millis(10));
+                // Clones are dropped on this thread without waking
+                drop(waker.clone());
+                waker.wake_by_ref();
+                waker.wake();
+            }
+        });
+        let handle = spawn_local(async move {
+            let mut polls = 0;
+            future::poll_fn(|cx| {
+                polls += 1;
+                if polls == 3 {
+                    return Poll::Ready(polls);
+                }
+                sender.send(cx.waker().clone()).unwrap();
+                Poll::Pending
+            })
+            .await
+        });
+        let res = handle.await.unwrap();
+        helper.join().unwrap();
+        res
+    });
+    assert_eq!(res, 3)
+}
+
+#[test]
+fn executors_have_unique_ids() {
+    let first = LocalExecutor::default();
+    let second = LocalExecutor::default();
+    assert_ne!(first.get_id(), second.get_id());
+    let id = first.run(async { executor().id() });
+    assert_eq!(id, first.get_id());
+}
+
+#[test]
+fn wake_from_another_executor() {
+    let local_ex = LocalExecutor::default();
+    let res = local_ex.run(async {
+        let (sender, receiver) = mpsc::channel::<Waker>();
+        let other = thread::spawn(move || {
+            let other_ex = LocalExecutor::default();
+            other_ex.run(async move {
+                for waker in receiver {
+                    // Let the other executor poll something of its own first
+                    spawn_local(async {}).await.unwrap();
+                    waker.wake();
+                }
+            })
+        });
+        let mut polls = 0;
+        let res = future::poll_fn(|cx| {
+            polls += 1;
+            if polls == 3 {
+                return Poll::Ready(polls);
+            }
+            sender.send(cx.waker().clone()).unwrap();
+            Poll::Pending
+        })
+        .await;
+        drop(sender);
+        other.join().unwrap();
+        res
+    });
+    assert_eq!(res, 3)
+}
+
+#[test]
+fn wake_after_executor_is_gone() {
+    struct SetOnDrop(Rc<Cell<bool>>);
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let dropped = Rc::new(Cell::new(false));
+    let local_ex = LocalExecutor::default();
+    let guard = SetOnDrop(dropped.clone());
+    let (waker, handle) = local_ex.run(async move {
+        let (sender, receiver) = mpsc::channel::<Waker>();
+        let handle = spawn_local(async move {
+            let _guard = guard;
+            future::poll_fn(|cx| {
+                sender.send(cx.waker().clone()).unwrap();
+                Poll::<()>::Pending
+            })
+            .await
+        });
+        loop {
+            if let Ok(waker) = receiver.try_recv() {
+                break (waker, handle);
+            }
+            yield_now().await;
+        }
+    });
+    drop(local_ex);
+
+    // The waker's reference must be dropped rather than leaked, so that the
+    // task goes away with its `JoinHandle`
+    thread::spawn(move || waker.wake()).join().unwrap();
+    assert!(!dropped.get());
+    drop(handle);
+    assert!(dropped.get());
+}
+
+#[test]
+fn pool_runs_one_executor_per_thread() {
+    let handles = LocalExecutorPoolBuilder::new(3, Placement::Unbound)
+        .on_all_shards(|| async {
+            let handle = spawn_local(async { executor().id() });
+            (handle.await.unwrap(), thread::current().id())
+        })
+        .unwrap();
+    let results: Vec<_> = handles
+        .join_all()
+        .into_iter()
+        .map(|res| res.unwrap())
+        .collect();
+    assert_eq!(results.len(), 3);
+    for (i, first) in results.iter().enumerate() {
+        for second in &results[i + 1..] {
+            assert_ne!(first.0, second.0);
+            assert_ne!(first.1, second.1);
+        }
+    }
+}
+
+#[test]
+fn pool_executors_share_the_configuration() {
+    let handles = LocalExecutorPoolBuilder::new(2, Placement::Unbound)
+        .name("shard")
+        .preempt_timer(Duration::from_millis(10))
+        .stack_size(1 << 20)
+        .on_all_shards(|| async {
+            (
+                LOCAL_EX.with(|local_ex| local_ex.name().to_owned()),
+                thread::current().name().map(String::from),
+            )
+        })
+        .unwrap();
+    let names: Vec<_> = handles
+        .join_all()
+        .into_iter()
+        .map(|res| res.unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("shard-0".to_owned(), Some("shard-0".to_owned())),
+            ("shard-1".to_owned(), Some("shard-1".to_owned())),
+        ]
+    );
+
+    let err = LocalExecutorPoolBuilder::new(2, Placement::Unbound)
+        .ring_depth(0)
+        .on_all_shards(|| async {})
+        .unwrap_err();
+    assert!(matches!(err, BuilderError::InvalidRingDepth(0)));
+}
+
+#[test]
+fn pool_join_all_collects_panics() {
+    let shard = Arc::new(AtomicUsize::new(0));
+    let handles = LocalExecutorPoolBuilder::new(2, Placement::Fixed(0))
+        .on_all_shards(move || async move {
+            if shard.fetch_add(1, Ordering::Relaxed) == 0 {
+                panic!("first shard panicked");
+            }
+        })
+        .unwrap();
+    let results = handles.join_all();
+    assert_eq!(results.iter().filter(|res| res.is_err()).count(), 1);
+}
+
+#[test]
+fn local_executor_builder_configuration() {
+    let local_ex = LocalExecutorBuilder::new(Placement::Unbound)
+        .name("configured")
+        .ring_depth(16)
+        .preempt_timer(Duration::from_millis(10))
+        .spin_before_park(Duration::from_millis(1))
+        .build()
+        .unwrap();
+    assert_eq!(local_ex.name(), "configured");
+    let res = local_ex.run(async {
+        // The timer wakes the task while the executor is spinning, and then
+        // again after it parked
+        let (sender, receiver) = mpsc::channel::<Waker>();
+        thread::spawn(move || {
+            for waker in receiver {
+                thread::sleep(Duration::from_millis(5));
+                waker.wake();
+            }
+        });
+        let mut polls = 0;
+        future::poll_fn(|cx| {
+            polls += 1;
+            if polls == 3 {
+                return Poll::Ready(polls);
+            }
+            sender.send(cx.waker().clone()).unwrap();
+            Poll::Pending
+        })
+        .await
+    });
+    assert_eq!(res, 3);
+}
+
+#[test]
+fn local_executor_builder_rejects_invalid_configuration() {
+    let err = LocalExecutorBuilder::new(Placement::Unbound)
+        .ring_depth(0)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, BuilderError::InvalidRingDepth(0)));
+    let err = LocalExecutorBuilder::new(Placement::Unbound)
+        .preempt_timer(Duration::ZERO)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, BuilderError::InvalidPreemptTimer(_)));
+    let err = LocalExecutorBuilder::new(Placement::Unbound)
+        .name("nul\0")
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, BuilderError::InvalidName(_)));
+    let err = LocalExecutorBuilder::new(Placement::Custom(vec![]))
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, BuilderError::Placement(_)));
+}
+
+#[test]
+fn local_executor_builder_spawn() {
+    let handle = LocalExecutorBuilder::new(Placement::Fixed(0))
+        .name("spawned")
+        .stack_size(1 << 20)
+        .spawn(|| async {
+            let task = spawn_local(async { thread::current().name().map(String::from) });
+            (executor().id(), task.await.unwrap())
+        })
+        .unwrap();
+    assert_eq!(handle.thread().name(), Some("spawned"));
+    let (id, thread_name) = handle.join().unwrap();
+    assert_eq!(thread_name.as_deref(), Some("spawned"));
+    assert!(registry::notifiers().iter().all(|(other, _)| *other != id));
+
+    let handle = LocalExecutorBuilder::new(Placement::Unbound)
+        .spawn(|| async { panic!("executor panicked") })
+        .unwrap();
+    let payload = handle.join().unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"executor panicked"));
+}
+
+#[test]
+fn task_panic_is_isolated() {
+    let panics = Arc::new(AtomicUsize::new(0));
+    let hook_panics = panics.clone();
+    let local_ex = LocalExecutorBuilder::new(Placement::Unbound)
+        .on_task_panic(move |payload| {
+            assert_eq!(payload.downcast_ref::<&str>(), Some(&"task panicked"));
+            hook_panics.fetch_add(1, Ordering::Relaxed);
+        })
+        .build()
+        .unwrap();
+    let res = local_ex.run(async {
+        let survivor = spawn_local(async {
+            yield_now().await;
+            1
+        });
+        let panicked = spawn_local(async {
+            yield_now().await;
+            panic!("task panicked");
+        });
+        // Nobody awaits this one, the executor must survive it anyway
+        drop(spawn_local(async { panic!("task panicked") }));
+
+        let payload = match panicked.await {
+            Err(JoinError::Panicked(payload)) => payload,
+            res => panic!("Expected a panic, got {:?}", res.map(|_| ())),
+        };
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"task panicked"));
+        survivor.await.unwrap() + 1
+    });
+    assert_eq!(res, 2);
+    assert_eq!(panics.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn join_error_distinguishes_panics() {
+    let local_ex = LocalExecutor::default();
+    let err = local_ex.run(async {
+        spawn_local(async { panic!("task panicked with {}", std::hint::black_box(42)) })
+            .await
+            .unwrap_err()
+    });
+    assert!(err.is_panic());
+    assert!(!err.is_cancelled());
+    assert_eq!(err.to_string(), "task panicked: task panicked with 42");
+    let payload = err.into_panic();
+    assert_eq!(
+        payload.downcast_ref::<String>().map(String::as_str),
+        Some("task panicked with 42")
+    );
+
+    let err = JoinError::Cancelled;
+    assert_eq!(err.to_string(), "task was cancelled");
+    assert!(err.try_into_panic().unwrap_err().is_cancelled());
+}
+
+#[test]
+fn run_raises_the_original_panic() {
+    let payload = std::panic::catch_unwind(|| {
+        LocalExecutor::default().run(async { std::panic::panic_any(7usize) })
+    })
+    .unwrap_err();
+    assert_eq!(payload.downcast_ref::<usize>(), Some(&7));
+}
+
+/// Sets the flag when dropped, to tell when the future of a task is gone.
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+#[test]
+fn join_handle_cancel() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        // A task that is never woken again
+        let dropped = Rc::new(Cell::new(false));
+        let flag = DropFlag(dropped.clone());
+        let handle = spawn_local(async move {
+            let _flag = flag;
+            future::pending::<()>().await
+        });
+        yield_now().await;
+        assert!(!handle.is_finished());
+        assert!(handle.cancel().await.unwrap_err().is_cancelled());
+        assert!(dropped.get());
+
+        // Cancelling a completed task gives its output back
+        let handle = spawn_local(async { 5 });
+        yield_now().await;
+        assert!(handle.is_finished());
+        assert_eq!(handle.cancel().await.unwrap(), 5);
+
+        // Cancelling a task that panicked gives its payload back
+        let handle = spawn_local(async { std::panic::panic_any(7usize) });
+        yield_now().await;
+        let payload = handle.cancel().await.unwrap_err().into_panic();
+        assert_eq!(payload.downcast_ref::<usize>(), Some(&7));
+    });
+}
+
+#[test]
+fn join_handle_abort() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let dropped = Rc::new(Cell::new(false));
+        let flag = DropFlag(dropped.clone());
+        let handle = spawn_local(async move {
+            let _flag = flag;
+            loop {
+                yield_now().await;
+            }
+        });
+        yield_now().await;
+        handle.abort();
+        assert!(!handle.is_finished());
+        assert!(handle.await.unwrap_err().is_cancelled());
+        assert!(dropped.get());
+    });
+}
+
+#[test]
+fn join_handle_detach() {
+    let local_ex = LocalExecutor::default();
+    let done = Rc::new(Cell::new(false));
+    let task_done = done.clone();
+    local_ex.run(async move {
+        spawn_local(async move {
+            yield_now().await;
+            task_done.set(true);
+        })
+        .detach();
+        while !done.get() {
+            yield_now().await;
+        }
+    });
+}
+
+#[test]
+fn tasks_have_increasing_ids_and_names() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let outer = current_task_id().unwrap();
+        let first = spawn_local(async { current_task_id().unwrap() });
+        let background = executor().create_task_queue("background", 100, Latency::NotImportant);
+        let second = TaskBuilder::new()
+            .name("worker")
+            .task_queue(background)
+            .spawn_local(async move {
+                assert_eq!(executor().current_task_queue(), background);
+                current_task_id().unwrap()
+            });
+        let third = spawn_named("named", async {});
+        assert!(outer < first.id() && first.id() < second.id() && second.id() < third.id());
+        assert_eq!(first.name(), None);
+        assert_eq!(second.name(), Some("worker"));
+        assert_eq!(third.name(), Some("named"));
+
+        let (first_id, second_id) = (first.id(), second.id());
+        assert_eq!(first.await.unwrap(), first_id);
+        assert_eq!(second.await.unwrap(), second_id);
+        // Awaiting polls the other tasks, but the id is back to ours after
+        assert_eq!(current_task_id(), Some(outer));
+    });
+    assert_eq!(current_task_id(), None);
+}
+
+#[test]
+fn dump_tasks_lists_live_tasks() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let outer = current_task_id().unwrap();
+        let background = executor().create_task_queue("background", 100, Latency::NotImportant);
+        let idle = TaskBuilder::new()
+            .name("idle")
+            .task_queue(background)
+            .spawn_local(future::pending::<()>());
+        yield_now().await;
+
+        let dump = local_ex.dump_tasks();
+        assert_eq!(dump.executor_id, local_ex.get_id());
+        assert_eq!(dump.tasks.len(), 2);
+        let (current, task) = (&dump.tasks[0], &dump.tasks[1]);
+        assert_eq!(current.id, outer);
+        assert!(current.state.running);
+        assert_eq!(task.id, idle.id());
+        assert_eq!(task.name.as_deref(), Some("idle"));
+        assert_eq!(task.task_queue, background);
+        assert!(!task.state.scheduled && !task.state.running && !task.state.closed);
+        assert!(task.state.handle);
+        assert_eq!(task.polls, 1);
+        assert!(task.since_last_poll.is_some());
+        assert!(dump.to_string().contains("\"idle\""));
+
+        assert!(idle.cancel().await.is_err());
+        assert_eq!(local_ex.live_tasks(), 1);
+    });
+    assert_eq!(local_ex.live_tasks(), 0);
+}
+
+#[test]
+fn dump_tasks_on_signal() {
+    task_dump::dump_tasks_on_signal(Signal::SIGUSR1).unwrap();
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let before = local_ex.dump_generation.get();
+        signal::raise(Signal::SIGUSR1).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while local_ex.dump_generation.get() == before {
+            assert!(
+                Instant::now() < deadline,
+                "the signal didn't trigger a dump"
+            );
+            yield_now().await;
+        }
+    });
+}
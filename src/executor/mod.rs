@@ -1,18 +1,36 @@
-use std::{future::Future, rc::Rc};
+use std::{any::Any, future::Future, rc::Rc, task::Poll};
+
+use futures_lite::future;
 
 use crate::{
     reactor::Reactor,
     task::{join_handle::JoinHandle, task::Task},
 };
 
-use self::{local_executor::LocalExecutor, task_queue::TaskQueueHandle};
+use self::{
+    local_executor::LocalExecutor,
+    task_builder::TaskBuilder,
+    task_queue::{Latency, TaskQueueHandle},
+    task_queue_stats::TaskQueueStats,
+};
 
+pub mod join_set;
+mod join_set_test;
 pub mod local_executor;
 pub mod local_executor_builder;
+pub mod local_executor_pool_builder;
 mod local_executor_test;
 pub mod placement;
+mod placement_test;
 pub mod queue_manager;
+pub(crate) mod registry;
+pub mod scope;
+mod scope_test;
+pub mod task_builder;
+pub mod task_dump;
 pub mod task_queue;
+pub mod task_queue_stats;
+mod task_queue_test;
 
 scoped_tls::scoped_thread_local!(static LOCAL_EX: LocalExecutor);
 
@@ -23,6 +41,61 @@ where
     executor().spawn_local(future)
 }
 
+/// Spawns a task with the given name onto the current executor. The name
+/// can be read back from the task's `JoinHandle`.
+pub fn spawn_named<T>(name: &str, future: impl Future<Output = T> + 'static) -> JoinHandle<T>
+where
+    T: 'static,
+{
+    TaskBuilder::new().name(name).spawn_local(future)
+}
+
+/// Spawns a task onto the `TaskQueue` identified by `handle` of the current
+/// executor.
+pub fn spawn_local_into<T>(
+    future: impl Future<Output = T> + 'static,
+    handle: TaskQueueHandle,
+) -> JoinHandle<T>
+where
+    T: 'static,
+{
+    executor().spawn_local_into(future, handle)
+}
+
+/// Gives the CPU back to the executor if its preemption timer fired, or if a
+/// task yielded, since it last polled for I/O. Long-running tasks should call
+/// this periodically.
+pub async fn yield_if_needed() {
+    if executor().need_preempt() {
+        yield_now().await
+    }
+}
+
+/// Unconditionally gives the CPU back to the executor. The task is
+/// rescheduled and runs again after the executor has polled for I/O and the
+/// other `TaskQueue`s had their chance to run.
+pub async fn yield_now() {
+    let mut yielded = false;
+    future::poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        LOCAL_EX.with(|local_ex| local_ex.request_yield());
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+/// Hands the payload of a task that panicked to the panic hook of the
+/// current executor, if it has one.
+pub(crate) fn report_task_panic(payload: &(dyn Any + Send)) {
+    if LOCAL_EX.is_set() {
+        LOCAL_EX.with(|local_ex| local_ex.report_task_panic(payload));
+    }
+}
+
 pub(crate) fn executor_id() -> Option<usize> {
     if LOCAL_EX.is_set() {
         Some(LOCAL_EX.with(|ex| ex.get_id()))
@@ -31,11 +104,12 @@ pub(crate) fn executor_id() -> Option<usize> {
     }
 }
 
-pub(crate) fn executor() -> ExecutorProxy {
+/// Returns a proxy to the `LocalExecutor` running on the current thread.
+pub fn executor() -> ExecutorProxy {
     ExecutorProxy {}
 }
 
-pub(crate) struct ExecutorProxy {}
+pub struct ExecutorProxy {}
 
 impl ExecutorProxy {
     pub fn spawn_local<T>(&self, future: impl Future<Output = T> + 'static) -> JoinHandle<T>
@@ -45,9 +119,48 @@ impl ExecutorProxy {
         LOCAL_EX.with(|local_ex| local_ex.spawn(future))
     }
 
+    pub fn spawn_local_into<T>(
+        &self,
+        future: impl Future<Output = T> + 'static,
+        handle: TaskQueueHandle,
+    ) -> JoinHandle<T>
+    where
+        T: 'static,
+    {
+        LOCAL_EX.with(|local_ex| local_ex.spawn_into(future, handle))
+    }
+
+    pub fn create_task_queue(
+        &self,
+        name: &str,
+        shares: usize,
+        latency: Latency,
+    ) -> TaskQueueHandle {
+        LOCAL_EX.with(|local_ex| local_ex.create_task_queue(name, shares, latency))
+    }
+
+    /// Returns the id of the current executor, which is unique across all
+    /// executors of the process.
+    pub fn id(&self) -> usize {
+        LOCAL_EX.with(|local_ex| local_ex.get_id())
+    }
+
+    pub fn need_preempt(&self) -> bool {
+        LOCAL_EX.with(|local_ex| local_ex.need_preempt())
+    }
+
+    /// Returns the counters of the `TaskQueue` identified by `handle`, or
+    /// `None` if it doesn't belong to the current executor.
+    pub fn task_queue_stats(&self, handle: TaskQueueHandle) -> Option<TaskQueueStats> {
+        LOCAL_EX.with(|local_ex| local_ex.task_queue_stats(handle))
+    }
+
     pub fn current_task_queue(&self) -> TaskQueueHandle {
-        todo!()
-        // return LOCAL_EX.with(|local_ex| local_ex.current_task_queue());
+        LOCAL_EX.with(|local_ex| {
+            local_ex
+                .current_task_queue()
+                .expect("current_task_queue called outside of a task")
+        })
     }
 }
 
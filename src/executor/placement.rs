@@ -1,3 +1,46 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use crate::sys::{cpu_topology, CpuLocation};
+
+/// A set of CPUs that an executor may run on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CpuSet {
+    cpus: BTreeSet<usize>,
+}
+
+impl CpuSet {
+    pub fn new(cpus: impl IntoIterator<Item = usize>) -> Self {
+        CpuSet {
+            cpus: cpus.into_iter().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpus.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cpus.len()
+    }
+
+    pub fn contains(&self, cpu: usize) -> bool {
+        self.cpus.contains(&cpu)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.cpus.iter().copied()
+    }
+}
+
+impl FromIterator<usize> for CpuSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        CpuSet::new(iter)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Placement {
     /// The `Unbound` variant creates a [`LocalExecutor`]s that are not bound to
@@ -6,4 +49,139 @@ pub enum Placement {
     /// The [`LocalExecutor`] is bound to the CPU specified by
     /// `Fixed`.
     Fixed(usize),
+    /// Each [`LocalExecutor`] is bound to a single CPU, picked to be as far
+    /// as possible from the CPUs already used: first on another NUMA node,
+    /// then on another package, then on another core. Hyperthreads of a core
+    /// that is already used are only picked once every core is taken.
+    MaxSpread,
+    /// Each [`LocalExecutor`] is bound to a single CPU, picked to be as close
+    /// as possible to the CPUs already used, filling the sibling hyperthreads
+    /// of a core before moving to the next core.
+    MaxPack,
+    /// Each [`LocalExecutor`] is bound to the CPUs of its own [`CpuSet`].
+    /// There must be exactly one set per executor.
+    Custom(Vec<CpuSet>),
+}
+
+impl Placement {
+    /// Returns the CPUs that each of `nr_executors` executors should be bound
+    /// to, or `None` for the executors that aren't bound to any CPU.
+    pub(crate) fn cpu_bindings(&self, nr_executors: usize) -> io::Result<Vec<Option<Vec<usize>>>> {
+        self.cpu_bindings_from(nr_executors, cpu_topology)
+    }
+
+    /// Same as [`Placement::cpu_bindings`], but reads the topology of the
+    /// machine with `topology` when the placement needs it.
+    pub(crate) fn cpu_bindings_from(
+        &self,
+        nr_executors: usize,
+        topology: impl FnOnce() -> io::Result<Vec<CpuLocation>>,
+    ) -> io::Result<Vec<Option<Vec<usize>>>> {
+        let bindings = match self {
+            Placement::Unbound => vec![None; nr_executors],
+            Placement::Fixed(cpu) => vec![Some(vec![*cpu]); nr_executors],
+            Placement::MaxSpread => one_cpu_each(spread_order(&topology()?), nr_executors)?,
+            Placement::MaxPack => one_cpu_each(pack_order(&topology()?), nr_executors)?,
+            Placement::Custom(cpu_sets) => {
+                if cpu_sets.len() != nr_executors {
+                    return Err(invalid_placement(format!(
+                        "Placement::Custom has {} CpuSets for {} executors",
+                        cpu_sets.len(),
+                        nr_executors
+                    )));
+                }
+                if cpu_sets.iter().any(CpuSet::is_empty) {
+                    return Err(invalid_placement("Placement::Custom has an empty CpuSet"));
+                }
+                cpu_sets
+                    .iter()
+                    .map(|cpu_set| Some(cpu_set.iter().collect()))
+                    .collect()
+            }
+        };
+        Ok(bindings)
+    }
+}
+
+/// Binds each executor to the next CPU of `order`, wrapping around if there
+/// are more executors than CPUs.
+fn one_cpu_each(order: Vec<usize>, nr_executors: usize) -> io::Result<Vec<Option<Vec<usize>>>> {
+    if order.is_empty() {
+        return Err(invalid_placement("No online CPU to place executors on"));
+    }
+    Ok(order
+        .into_iter()
+        .cycle()
+        .take(nr_executors)
+        .map(|cpu| Some(vec![cpu]))
+        .collect())
+}
+
+/// Orders the CPUs so that hyperthreads of the same core are next to each
+/// other, and cores of the same package and NUMA node are next to each other.
+fn pack_order(topology: &[CpuLocation]) -> Vec<usize> {
+    let mut cpus = topology.to_vec();
+    cpus.sort();
+    cpus.into_iter().map(|location| location.cpu).collect()
+}
+
+/// Orders the CPUs so that each prefix of the order is spread as evenly as
+/// possible across NUMA nodes, then packages, then cores. Every core comes
+/// once before any of them comes again through a sibling hyperthread.
+fn spread_order(topology: &[CpuLocation]) -> Vec<usize> {
+    let levels: [fn(&CpuLocation) -> usize; 3] = [
+        |location| location.numa_node,
+        |location| location.package,
+        |location| location.core,
+    ];
+
+    // Ranks the hyperthreads of each core, and spreads every rank on its own
+    let mut cpus = topology.to_vec();
+    cpus.sort();
+    let mut next_rank: BTreeMap<(usize, usize, usize), usize> = BTreeMap::new();
+    let mut ranks: Vec<Vec<CpuLocation>> = Vec::new();
+    for location in cpus {
+        let rank = next_rank
+            .entry((location.numa_node, location.package, location.core))
+            .or_default();
+        if *rank == ranks.len() {
+            ranks.push(Vec::new());
+        }
+        ranks[*rank].push(location);
+        *rank += 1;
+    }
+    ranks
+        .into_iter()
+        .flat_map(|cpus| spread_level(cpus, &levels))
+        .collect()
+}
+
+/// Groups `cpus` by the first level and interleaves the spread order of
+/// each group, so that consecutive CPUs come from different groups.
+fn spread_level(mut cpus: Vec<CpuLocation>, levels: &[fn(&CpuLocation) -> usize]) -> Vec<usize> {
+    let Some((level, rest)) = levels.split_first() else {
+        cpus.sort();
+        return cpus.into_iter().map(|location| location.cpu).collect();
+    };
+    let mut groups: BTreeMap<usize, Vec<CpuLocation>> = BTreeMap::new();
+    for location in cpus {
+        groups.entry(level(&location)).or_default().push(location);
+    }
+    let mut groups: Vec<_> = groups
+        .into_values()
+        .map(|group| spread_level(group, rest).into_iter())
+        .collect();
+
+    let mut order = Vec::new();
+    loop {
+        let len = order.len();
+        order.extend(groups.iter_mut().filter_map(Iterator::next));
+        if order.len() == len {
+            return order;
+        }
+    }
+}
+
+fn invalid_placement(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.into())
 }
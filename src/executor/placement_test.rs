@@ -0,0 +1,187 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::sys::{parse_cpu_list, read_topology, CpuLocation};
+
+use super::placement::{CpuSet, Placement};
+
+/// A fake `/sys/devices/system/cpu` tree in a temporary directory.
+struct SysfsFixture {
+    root: PathBuf,
+}
+
+impl SysfsFixture {
+    /// Creates a tree with the given CPUs online.
+    fn new(name: &str, cpus: &[CpuLocation]) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "mini-async-runtime-sysfs-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        let online: Vec<_> = cpus.iter().map(|cpu| cpu.cpu.to_string()).collect();
+        write(&root.join("online"), &online.join(","));
+        for cpu in cpus {
+            let cpu_dir = root.join(format!("cpu{}", cpu.cpu));
+            write(
+                &cpu_dir.join("topology/physical_package_id"),
+                &cpu.package.to_string(),
+            );
+            write(&cpu_dir.join("topology/core_id"), &cpu.core.to_string());
+            fs::create_dir_all(cpu_dir.join(format!("node{}", cpu.numa_node))).unwrap();
+        }
+        SysfsFixture { root }
+    }
+
+    /// Overwrites the package id the kernel reports for `cpu`.
+    fn set_package_id(&self, cpu: usize, id: &str) {
+        write(
+            &self
+                .root
+                .join(format!("cpu{}/topology/physical_package_id", cpu)),
+            id,
+        );
+    }
+
+    fn bindings(&self, placement: &Placement, nr_executors: usize) -> Vec<Option<Vec<usize>>> {
+        placement
+            .cpu_bindings_from(nr_executors, || read_topology(&self.root))
+            .unwrap()
+    }
+}
+
+impl Drop for SysfsFixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, format!("{}\n", contents)).unwrap();
+}
+
+/// Two NUMA nodes with one package each. Every package has two cores with two
+/// hyperthreads, numbered the way Linux usually does: the first hyperthread
+/// of every core comes before the second ones.
+fn two_node_smt_machine() -> Vec<CpuLocation> {
+    (0..8)
+        .map(|cpu| CpuLocation {
+            numa_node: (cpu / 2) % 2,
+            package: (cpu / 2) % 2,
+            core: cpu % 2,
+            cpu,
+        })
+        .collect()
+}
+
+fn single_cpus(cpus: &[usize]) -> Vec<Option<Vec<usize>>> {
+    cpus.iter().map(|cpu| Some(vec![*cpu])).collect()
+}
+
+#[test]
+fn parse_kernel_cpu_lists() {
+    assert_eq!(
+        parse_cpu_list("0-3,6,8-9\n").unwrap(),
+        vec![0, 1, 2, 3, 6, 8, 9]
+    );
+    assert_eq!(parse_cpu_list("5").unwrap(), vec![5]);
+    assert!(parse_cpu_list("\n").unwrap().is_empty());
+    assert!(parse_cpu_list("0-x").is_err());
+}
+
+#[test]
+fn read_topology_from_sysfs() {
+    let machine = two_node_smt_machine();
+    let sysfs = SysfsFixture::new("read", &machine);
+    assert_eq!(read_topology(&sysfs.root).unwrap(), machine);
+}
+
+#[test]
+fn unknown_packages_are_read_as_package_zero() {
+    // One node and package with two cores of two hyperthreads, whose package
+    // the kernel doesn't know
+    let machine: Vec<_> = (0..4)
+        .map(|cpu| CpuLocation {
+            numa_node: 0,
+            package: 0,
+            core: cpu % 2,
+            cpu,
+        })
+        .collect();
+    let sysfs = SysfsFixture::new("unknown-package", &machine);
+    for cpu in 0..4 {
+        sysfs.set_package_id(cpu, "-1");
+    }
+    assert_eq!(read_topology(&sysfs.root).unwrap(), machine);
+    assert_eq!(
+        sysfs.bindings(&Placement::MaxSpread, 4),
+        single_cpus(&[0, 1, 2, 3])
+    );
+    assert_eq!(
+        sysfs.bindings(&Placement::MaxPack, 4),
+        single_cpus(&[0, 2, 1, 3])
+    );
+}
+
+#[test]
+fn max_spread_avoids_sharing_nodes_and_cores() {
+    let sysfs = SysfsFixture::new("spread", &two_node_smt_machine());
+    // cpu 0 and 4 are the two hyperthreads of core 0 on node 0, cpu 2 is on
+    // node 1. Node 0 and node 1 alternate, and hyperthreads come last.
+    assert_eq!(
+        sysfs.bindings(&Placement::MaxSpread, 8),
+        single_cpus(&[0, 2, 1, 3, 4, 6, 5, 7])
+    );
+    assert_eq!(
+        sysfs.bindings(&Placement::MaxSpread, 10),
+        single_cpus(&[0, 2, 1, 3, 4, 6, 5, 7, 0, 2])
+    );
+}
+
+#[test]
+fn max_spread_takes_every_core_before_siblings() {
+    // Node 0 has three cores and node 1 only one, all of them with two
+    // hyperthreads. cpu 4 to 7 are the siblings of cpu 0 to 3.
+    let machine: Vec<_> = (0..8)
+        .map(|cpu| {
+            let numa_node = usize::from(cpu % 4 == 3);
+            CpuLocation {
+                numa_node,
+                package: numa_node,
+                core: if numa_node == 0 { cpu % 4 } else { 0 },
+                cpu,
+            }
+        })
+        .collect();
+    let sysfs = SysfsFixture::new("asymmetric", &machine);
+    assert_eq!(
+        sysfs.bindings(&Placement::MaxSpread, 8),
+        single_cpus(&[0, 3, 1, 2, 4, 7, 5, 6])
+    );
+}
+
+#[test]
+fn max_pack_fills_hyperthreads_first() {
+    let sysfs = SysfsFixture::new("pack", &two_node_smt_machine());
+    assert_eq!(
+        sysfs.bindings(&Placement::MaxPack, 5),
+        single_cpus(&[0, 4, 1, 5, 2])
+    );
+}
+
+#[test]
+fn custom_placement_uses_one_set_per_executor() {
+    let placement = Placement::Custom(vec![CpuSet::new([0, 1]), CpuSet::new([3])]);
+    let bindings = placement
+        .cpu_bindings_from(2, || panic!("Custom doesn't need the topology"))
+        .unwrap();
+    assert_eq!(bindings, vec![Some(vec![0, 1]), Some(vec![3])]);
+
+    assert!(placement.cpu_bindings_from(3, || unreachable!()).is_err());
+    assert!(Placement::Custom(vec![CpuSet::default()])
+        .cpu_bindings_from(1, || unreachable!())
+        .is_err());
+}
@@ -1,14 +1,19 @@
-use std::{cell::RefCell, collections::BinaryHeap, rc::Rc};
+use std::{cell::RefCell, collections::BinaryHeap, rc::Rc, time::Duration};
 
 use ahash::AHashMap;
 
-use super::task_queue::TaskQueue;
+use super::task_queue::{Latency, TaskQueue};
 
 #[derive(Debug)]
 pub(crate) struct QueueManager {
     pub active_queues: BinaryHeap<Rc<RefCell<TaskQueue>>>,
     pub active_executing: Option<Rc<RefCell<TaskQueue>>>,
     pub available_queues: AHashMap<usize, Rc<RefCell<TaskQueue>>>,
+    // Index that will be given to the next TaskQueue that is created
+    pub next_queue_index: usize,
+    // vruntime of the last queue that ran. Queues that become active again
+    // start from here so that they can't monopolize the CPU after idling.
+    pub last_vruntime: u64,
 }
 
 impl QueueManager {
@@ -17,15 +22,50 @@ impl QueueManager {
             active_queues: BinaryHeap::new(),
             active_executing: None,
             available_queues: AHashMap::new(),
+            next_queue_index: 0,
+            last_vruntime: 0,
         }
     }
 
     pub(crate) fn maybe_activate_queue(&mut self, queue: Rc<RefCell<TaskQueue>>) {
         let mut state = queue.borrow_mut();
         if !state.is_active() {
+            state.vruntime = state.vruntime.max(self.last_vruntime);
             state.active = true;
+            state.mark_runnable(true);
             drop(state);
             self.active_queues.push(queue);
         }
     }
+
+    /// Records the vruntime of the queue that just ran. `None` means that
+    /// its vruntime overflowed, in which case every queue starts over from
+    /// zero.
+    pub(crate) fn update_last_vruntime(&mut self, vruntime: Option<u64>) {
+        self.last_vruntime = match vruntime {
+            Some(vruntime) => vruntime,
+            None => {
+                for queue in self.available_queues.values() {
+                    queue.borrow_mut().vruntime = 0;
+                }
+                0
+            }
+        };
+    }
+
+    /// Returns how long tasks may run before the executor goes back to
+    /// polling I/O. Active latency sensitive queues shorten it so that their
+    /// tasks get scheduled within their latency, even while other queues are
+    /// busy.
+    pub(crate) fn preempt_timer_duration(&self, default: Duration) -> Duration {
+        self.available_queues
+            .values()
+            .map(|tq| tq.borrow())
+            .filter(|tq| tq.is_active())
+            .filter_map(|tq| match tq.latency {
+                Latency::Matters(d) => Some(d),
+                Latency::NotImportant => None,
+            })
+            .fold(default, Duration::min)
+    }
 }
@@ -0,0 +1,41 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use crate::sys::RemoteNotifier;
+
+/// Id that will be given to the next executor that is built.
+static NEXT_EXECUTOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Maps the id of every live executor to its notifier, so that other threads
+/// can interrupt it.
+static REGISTRY: Mutex<BTreeMap<usize, Weak<RemoteNotifier>>> = Mutex::new(BTreeMap::new());
+
+/// Allocates a globally unique id for a new executor and registers its
+/// notifier under that id.
+pub(crate) fn register(notifier: &Arc<RemoteNotifier>) -> usize {
+    let id = NEXT_EXECUTOR_ID.fetch_add(1, Ordering::Relaxed);
+    REGISTRY
+        .lock()
+        .unwrap()
+        .insert(id, Arc::downgrade(notifier));
+    id
+}
+
+pub(crate) fn unregister(id: usize) {
+    REGISTRY.lock().unwrap().remove(&id);
+}
+
+/// Returns the id and the notifier of every live executor.
+pub(crate) fn notifiers() -> Vec<(usize, Arc<RemoteNotifier>)> {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(id, notifier)| Some((*id, notifier.upgrade()?)))
+        .collect()
+}
@@ -0,0 +1,199 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use futures_lite::{future, pin};
+
+use super::join_set::ReadyQueue;
+
+type ChildFuture<'env> = Pin<Box<dyn Future<Output = ()> + 'env>>;
+
+/// Runs the future returned by `f` along with every future spawned through
+/// the [`Scope`] it's given, and resolves to the output of the former once
+/// all of them have completed.
+///
+/// Unlike [`spawn_local`](super::spawn_local), the spawned futures may borrow
+/// from the enclosing stack frame. They are polled by the task that awaits
+/// the scope rather than being tasks of their own, which is what guarantees
+/// that they never outlive it: when the scope's future is dropped, so are
+/// the spawned futures that didn't complete.
+pub async fn scope<'env, F, Fut, R>(f: F) -> R
+where
+    F: FnOnce(Scope<'env>) -> Fut,
+    Fut: Future<Output = R> + 'env,
+{
+    let scope = Scope {
+        inner: Rc::new(ScopeInner {
+            children: RefCell::new(BTreeMap::new()),
+            next_id: Cell::new(0),
+            ready: Arc::new(ReadyQueue::default()),
+            closed: Cell::new(false),
+        }),
+    };
+    // Children may hold clones of the scope, so they have to be dropped
+    // explicitly to break the cycle.
+    let _guard = CloseGuard(scope.clone());
+    let body = f(scope.clone());
+    pin!(body);
+    let mut output = None;
+    future::poll_fn(|cx| {
+        if output.is_none() {
+            if let Poll::Ready(out) = body.as_mut().poll(cx) {
+                output = Some(out);
+            }
+        }
+        scope.inner.poll_children(cx);
+        if output.is_some() && scope.inner.children.borrow().is_empty() {
+            return Poll::Ready(output.take().unwrap());
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Spawns futures that borrow from the stack frame of a call to [`scope`].
+pub struct Scope<'env> {
+    inner: Rc<ScopeInner<'env>>,
+}
+
+struct ScopeInner<'env> {
+    children: RefCell<BTreeMap<u64, Child<'env>>>,
+    next_id: Cell<u64>,
+    ready: Arc<ReadyQueue>,
+    // Set once the scope's future completed or was dropped
+    closed: Cell<bool>,
+}
+
+struct Child<'env> {
+    future: ChildFuture<'env>,
+    waker: Waker,
+}
+
+impl<'env> Scope<'env> {
+    /// Spawns `future` onto the scope. It starts running the next time the
+    /// scope's future is polled.
+    ///
+    /// Panics if the scope already completed.
+    pub fn spawn<T: 'env>(&self, future: impl Future<Output = T> + 'env) -> ScopedJoinHandle<T> {
+        assert!(
+            !self.inner.closed.get(),
+            "Can't spawn onto a scope that already completed"
+        );
+        let slot = Rc::new(RefCell::new(Slot {
+            value: None,
+            waiter: None,
+        }));
+        let child_slot = slot.clone();
+        let future = Box::pin(async move {
+            let value = future.await;
+            let mut slot = child_slot.borrow_mut();
+            slot.value = Some(value);
+            if let Some(waiter) = slot.waiter.take() {
+                waiter.wake();
+            }
+        });
+
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        let waker = self.inner.ready.waker(id);
+        // Wakes the scope's task so that the child gets its first poll
+        waker.wake_by_ref();
+        self.inner
+            .children
+            .borrow_mut()
+            .insert(id, Child { future, waker });
+        ScopedJoinHandle { slot }
+    }
+}
+
+impl<'env> ScopeInner<'env> {
+    /// Polls the children that were woken. Children woken while doing so,
+    /// like the ones that yield, are only polled the next time the scope's
+    /// task runs.
+    fn poll_children(&self, cx: &mut Context<'_>) {
+        for id in self.ready.take_all(cx) {
+            // The child is taken out while it's polled, so that it can spawn
+            // more children.
+            let Some(mut child) = self.children.borrow_mut().remove(&id) else {
+                continue;
+            };
+            let child_cx = &mut Context::from_waker(&child.waker);
+            if child.future.as_mut().poll(child_cx).is_pending() {
+                self.children.borrow_mut().insert(id, child);
+            }
+        }
+    }
+}
+
+impl<'env> Clone for Scope<'env> {
+    fn clone(&self) -> Self {
+        Scope {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'env> fmt::Debug for Scope<'env> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope")
+            .field("children", &self.inner.children.borrow().len())
+            .finish()
+    }
+}
+
+struct CloseGuard<'env>(Scope<'env>);
+
+impl<'env> Drop for CloseGuard<'env> {
+    fn drop(&mut self) {
+        let inner = &self.0.inner;
+        inner.closed.set(true);
+        // Dropping a child may drop the last handle to another one, so drop
+        // them outside of the borrow.
+        loop {
+            let children = mem::take(&mut *inner.children.borrow_mut());
+            if children.is_empty() {
+                break;
+            }
+            drop(children);
+        }
+    }
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    waiter: Option<Waker>,
+}
+
+/// Awaits the output of a future spawned with [`Scope::spawn`].
+pub struct ScopedJoinHandle<T> {
+    slot: Rc<RefCell<Slot<T>>>,
+}
+
+impl<T> Future for ScopedJoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                slot.waiter = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for ScopedJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedJoinHandle").finish()
+    }
+}
@@ -0,0 +1,100 @@
+use std::cell::{Cell, RefCell};
+
+use futures_lite::future;
+
+use crate::executor::yield_now;
+
+use super::{local_executor::LocalExecutor, scope::scope};
+
+#[test]
+fn scope_spawns_borrowing_futures() {
+    let local_ex = LocalExecutor::default();
+    let mut values = vec![1, 2, 3];
+    let sum = Cell::new(0);
+    let total = local_ex.run(async {
+        let total = scope(|s| {
+            let values = &values;
+            let sum = &sum;
+            async move {
+                let handles: Vec<_> = values
+                    .iter()
+                    .map(|value| {
+                        s.spawn(async move {
+                            yield_now().await;
+                            sum.set(sum.get() + value);
+                            value * 10
+                        })
+                    })
+                    .collect();
+                let mut total = 0;
+                for handle in handles {
+                    total += handle.await;
+                }
+                total
+            }
+        })
+        .await;
+        // Every child is done once the scope completed
+        assert_eq!(sum.get(), 6);
+        total
+    });
+    assert_eq!(total, 60);
+    // The borrows ended with the scope
+    values.push(4);
+}
+
+#[test]
+fn scope_waits_for_unawaited_children() {
+    let local_ex = LocalExecutor::default();
+    let log = RefCell::new(Vec::new());
+    local_ex.run(async {
+        let log = &log;
+        scope(|s| async move {
+            s.spawn(async move {
+                for _ in 0..3 {
+                    yield_now().await;
+                }
+                log.borrow_mut().push("child");
+            });
+            // Children may spawn more children
+            let inner = s.clone();
+            s.spawn(async move {
+                inner.spawn(async move { log.borrow_mut().push("grandchild") });
+            });
+            log.borrow_mut().push("body");
+        })
+        .await;
+        log.borrow_mut().push("after");
+    });
+    assert_eq!(*log.borrow(), vec!["body", "grandchild", "child", "after"]);
+}
+
+/// Sets the flag when dropped.
+struct DropFlag<'a>(&'a Cell<bool>);
+
+impl Drop for DropFlag<'_> {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+#[test]
+fn dropping_the_scope_drops_its_children() {
+    let local_ex = LocalExecutor::default();
+    let dropped = Cell::new(false);
+    local_ex.run(async {
+        let dropped = &dropped;
+        let scope = scope(|s| async move {
+            s.spawn(async move {
+                let _flag = DropFlag(dropped);
+                future::pending::<()>().await
+            });
+            future::pending::<()>().await
+        });
+        let mut scope = Box::pin(scope);
+        assert!(future::poll_once(scope.as_mut()).await.is_none());
+        assert!(!dropped.get());
+        drop(scope);
+        assert!(dropped.get());
+    });
+}
@@ -0,0 +1,38 @@
+use std::future::Future;
+
+use crate::task::join_handle::JoinHandle;
+
+use super::{task_queue::TaskQueueHandle, LOCAL_EX};
+
+/// Configures a task before spawning it onto the current executor.
+#[derive(Debug, Default)]
+pub struct TaskBuilder {
+    name: Option<String>,
+    task_queue: Option<TaskQueueHandle>,
+}
+
+impl TaskBuilder {
+    pub fn new() -> TaskBuilder {
+        TaskBuilder::default()
+    }
+
+    /// Names the task. The name can be read back from its `JoinHandle`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Spawns the task onto the `TaskQueue` identified by `handle` instead of
+    /// the current one.
+    pub fn task_queue(mut self, handle: TaskQueueHandle) -> Self {
+        self.task_queue = Some(handle);
+        self
+    }
+
+    pub fn spawn_local<T>(self, future: impl Future<Output = T> + 'static) -> JoinHandle<T>
+    where
+        T: 'static,
+    {
+        LOCAL_EX.with(|local_ex| local_ex.spawn_named(self.name, self.task_queue, future))
+    }
+}
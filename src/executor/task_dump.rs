@@ -0,0 +1,151 @@
+use std::{
+    fmt, io,
+    os::fd::{IntoRawFd, RawFd},
+    sync::{
+        atomic::{AtomicI32, AtomicU64, Ordering},
+        Once,
+    },
+    thread,
+};
+
+use nix::{
+    errno::Errno,
+    sys::{
+        eventfd::{eventfd, EfdFlags},
+        signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    },
+};
+
+use crate::task::live_tasks::TaskInfo;
+
+use super::registry;
+
+/// The live tasks of an executor, as returned by
+/// [`LocalExecutor::dump_tasks`](super::local_executor::LocalExecutor::dump_tasks).
+///
+/// Its `Display` implementation prints one line per task.
+#[derive(Debug, Clone)]
+pub struct TaskDump {
+    pub executor_id: usize,
+    pub executor_name: String,
+    /// Ordered by task id.
+    pub tasks: Vec<TaskInfo>,
+}
+
+impl fmt::Display for TaskDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "executor {} ({}): {} live tasks",
+            self.executor_id,
+            self.executor_name,
+            self.tasks.len()
+        )?;
+        for task in &self.tasks {
+            let state = [
+                (task.state.scheduled, 'S'),
+                (task.state.running, 'R'),
+                (task.state.completed, 'C'),
+                (task.state.closed, 'X'),
+                (task.state.handle, 'H'),
+            ]
+            .iter()
+            .map(|&(set, c)| if set { c } else { '-' })
+            .collect::<String>();
+            write!(
+                f,
+                "  task {} {:?} queue={} state={} refs={} polls={}",
+                task.id,
+                task.name.as_deref().unwrap_or(""),
+                task.task_queue.index,
+                state,
+                task.references,
+                task.polls,
+            )?;
+            match task.since_last_poll {
+                Some(elapsed) => writeln!(f, " last_poll={:?} ago", elapsed)?,
+                None => writeln!(f, " never polled")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bumped every time a dump is requested by a signal. Every executor prints
+/// its tasks once it sees a generation it didn't dump yet.
+static DUMP_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// The eventfd the signal handler writes to, or -1 before the first call to
+/// `dump_tasks_on_signal`.
+static SIGNAL_EVENTFD: AtomicI32 = AtomicI32::new(-1);
+
+static START_WATCHER: Once = Once::new();
+
+pub(crate) fn dump_generation() -> u64 {
+    DUMP_GENERATION.load(Ordering::Acquire)
+}
+
+/// Makes every executor of the process print its live tasks to stderr when
+/// the process receives `signal`.
+///
+/// The signal handler only writes to an eventfd. A watcher thread waits on
+/// it and wakes the executors, which print their tasks from their own thread
+/// the next time they go around their loop.
+pub fn dump_tasks_on_signal(signal: Signal) -> io::Result<()> {
+    start_watcher()?;
+    let action = SigAction::new(
+        SigHandler::Handler(handle_signal),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    unsafe { sigaction(signal, &action) }?;
+    Ok(())
+}
+
+fn start_watcher() -> io::Result<()> {
+    let mut res = Ok(());
+    START_WATCHER.call_once(|| res = spawn_watcher());
+    res?;
+    // The first call may have failed, and it is never retried
+    if SIGNAL_EVENTFD.load(Ordering::Acquire) < 0 {
+        return Err(io::Error::other("The task dump watcher failed to start"));
+    }
+    Ok(())
+}
+
+fn spawn_watcher() -> io::Result<()> {
+    // Never closed, the handler may run at any time
+    let fd = eventfd(0, EfdFlags::EFD_CLOEXEC)?.into_raw_fd();
+    thread::Builder::new()
+        .name(String::from("task-dump"))
+        .spawn(move || watch(fd))?;
+    SIGNAL_EVENTFD.store(fd, Ordering::Release);
+    Ok(())
+}
+
+fn watch(fd: RawFd) {
+    let mut buf = [0u8; 8];
+    loop {
+        match nix::unistd::read(fd, &mut buf) {
+            Ok(_) => {
+                DUMP_GENERATION.fetch_add(1, Ordering::AcqRel);
+                for (_, notifier) in registry::notifiers() {
+                    notifier.interrupt();
+                }
+            }
+            Err(Errno::EINTR) => {}
+            Err(err) => panic!("Failed to read from the task dump eventfd: {}", err),
+        }
+    }
+}
+
+extern "C" fn handle_signal(_: nix::libc::c_int) {
+    // `write` is async-signal-safe. The interrupted code may be about to
+    // read errno, so whatever `write` leaves in it is undone.
+    let saved = errno::errno();
+    let fd = SIGNAL_EVENTFD.load(Ordering::Acquire);
+    if fd >= 0 {
+        let _ = nix::unistd::write(fd, &1u64.to_ne_bytes());
+    }
+    errno::set_errno(saved);
+}
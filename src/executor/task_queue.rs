@@ -1,18 +1,44 @@
-use std::{borrow::BorrowMut, cell::RefCell, collections::VecDeque, future::Future, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::task::{
-    join_handle::JoinHandle,
-    task::{create_task, Task},
+use crate::{
+    sys::RemoteNotifier,
+    task::{
+        join_handle::JoinHandle,
+        live_tasks::LiveTasks,
+        task::{create_task, Task, TaskMeta},
+    },
 };
 
-use super::LOCAL_EX;
+use super::{task_queue_stats::TaskQueueStats, LOCAL_EX};
 
 /// Wrapper around an index that uniquely identifies a TaskQueue
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TaskQueueHandle {
     pub(crate) index: usize,
 }
 
+/// The default amount of shares given to a [`TaskQueue`], including the
+/// default one.
+pub const DEFAULT_SHARES: usize = 1000;
+
+/// Describes how sensitive the tasks of a [`TaskQueue`] are to scheduling
+/// delays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Latency {
+    /// Tasks in this queue should be scheduled within the given `Duration`
+    /// after they become runnable.
+    Matters(Duration),
+    /// Tasks in this queue only care about throughput.
+    NotImportant,
+}
+
 #[derive(Debug)]
 pub(crate) struct TaskQueue {
     // contains the actual queue of Tasks
@@ -20,36 +46,84 @@ pub(crate) struct TaskQueue {
     // The invariant around active is that when it's true,
     // it needs to be inside the active_executors
     pub(crate) active: bool,
+    // Relative amount of CPU this queue gets compared to other queues
+    pub(crate) shares: usize,
+    // Precomputed MAX_SHARES / shares, so that charging the vruntime
+    // doesn't need a division
+    reciprocal_shares: u64,
+    // Runtime of this queue scaled by its shares. The queue with the smallest
+    // vruntime is the next one to run.
+    pub(crate) vruntime: u64,
+    pub(crate) latency: Latency,
+    pub(crate) index: usize,
+    pub(crate) stats: TaskQueueStats,
+    // When the queue last became runnable, cleared once the executor picks it
+    pub(crate) runnable_since: Option<Instant>,
 }
 
 impl Eq for TaskQueue {}
 
+// `active_queues` is a max-heap, so the queue with the smallest vruntime
+// needs to compare as the greatest.
 impl Ord for TaskQueue {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        std::cmp::Ordering::Equal
+        other.vruntime.cmp(&self.vruntime)
     }
 }
 
 impl PartialOrd for TaskQueue {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(std::cmp::Ordering::Equal)
+        Some(self.cmp(other))
     }
 }
 
 impl PartialEq for TaskQueue {
     fn eq(&self, other: &Self) -> bool {
-        true
+        self.vruntime == other.vruntime
     }
 }
 
+/// Most shares a `TaskQueue` can have. Any more and its vruntime would stop
+/// growing.
+pub(crate) const MAX_SHARES: usize = 1 << 22;
+
 impl TaskQueue {
-    pub(crate) fn new(name: &str) -> Rc<RefCell<Self>> {
+    pub(crate) fn new(
+        index: usize,
+        name: &str,
+        shares: usize,
+        latency: Latency,
+    ) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(TaskQueue {
-            ex: Rc::new(TaskQueueExecutor::new(name)),
+            ex: Rc::new(TaskQueueExecutor::new()),
             active: false,
+            shares,
+            reciprocal_shares: (MAX_SHARES / shares) as u64,
+            vruntime: 0,
+            latency,
+            index,
+            stats: TaskQueueStats::new(name),
+            runnable_since: None,
         }))
     }
 
+    /// Charges the queue for having run for `delta`. The more shares a queue
+    /// has, the slower its vruntime grows.
+    ///
+    /// Returns the new vruntime, or `None` if it overflowed.
+    pub(crate) fn account_vruntime(&mut self, delta: Duration) -> Option<u64> {
+        let delta_scaled = self
+            .reciprocal_shares
+            .checked_mul(delta.as_nanos().try_into().ok()?)?
+            >> 12;
+        self.vruntime = self.vruntime.checked_add(delta_scaled)?;
+        Some(self.vruntime)
+    }
+
+    pub(crate) fn handle(&self) -> TaskQueueHandle {
+        TaskQueueHandle { index: self.index }
+    }
+
     pub fn get_task(&self) -> Option<Task> {
         self.ex.get_task()
     }
@@ -61,19 +135,33 @@ impl TaskQueue {
     pub(crate) fn reset_active(&mut self) {
         self.active = !self.ex.local_queue.is_empty();
     }
+
+    /// Records that the queue is runnable from now on. `activated` is true if
+    /// it had no runnable task before.
+    pub(crate) fn mark_runnable(&mut self, activated: bool) {
+        if activated {
+            self.stats.activations += 1;
+        }
+        self.runnable_since = Some(Instant::now());
+    }
+
+    /// Records that the executor picked the queue to run its tasks.
+    pub(crate) fn mark_picked(&mut self) {
+        if let Some(since) = self.runnable_since.take() {
+            self.stats.queue_delay.record(since.elapsed());
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct TaskQueueExecutor {
     local_queue: LocalQueue,
-    name: String,
 }
 
 impl TaskQueueExecutor {
-    pub(crate) fn new(name: &str) -> Self {
+    pub(crate) fn new() -> Self {
         TaskQueueExecutor {
             local_queue: LocalQueue::new(),
-            name: name.into(),
         }
     }
 
@@ -81,9 +169,19 @@ impl TaskQueueExecutor {
     fn create_task<T>(
         &self,
         executor_id: usize,
+        notifier: Arc<RemoteNotifier>,
+        name: Option<String>,
+        live_tasks: Rc<LiveTasks>,
         tq: Rc<RefCell<TaskQueue>>,
         future: impl Future<Output = T>,
     ) -> (Task, JoinHandle<T>) {
+        let meta = TaskMeta {
+            executor_id,
+            notifier,
+            name,
+            task_queue: tq.borrow().index,
+            live_tasks,
+        };
         let tq = Rc::downgrade(&tq);
         let schedule = move |task| {
             let tq = tq.upgrade();
@@ -100,7 +198,7 @@ impl TaskQueueExecutor {
                 }
             }
         };
-        create_task(executor_id, future, schedule)
+        create_task(meta, future, schedule)
     }
 
     pub fn get_task(&self) -> Option<Task> {
@@ -110,10 +208,13 @@ impl TaskQueueExecutor {
     pub(crate) fn spawn_and_schedule<T>(
         &self,
         executor_id: usize,
+        notifier: Arc<RemoteNotifier>,
+        name: Option<String>,
+        live_tasks: Rc<LiveTasks>,
         tq: Rc<RefCell<TaskQueue>>,
         future: impl Future<Output = T>,
     ) -> JoinHandle<T> {
-        let (task, handle) = self.create_task(executor_id, tq, future);
+        let (task, handle) = self.create_task(executor_id, notifier, name, live_tasks, tq, future);
         task.schedule();
         handle
     }
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+/// Counters of a [`TaskQueue`](super::task_queue::TaskQueue), as returned by
+/// [`ExecutorProxy::task_queue_stats`](super::ExecutorProxy::task_queue_stats).
+#[derive(Debug, Clone)]
+pub struct TaskQueueStats {
+    pub name: String,
+    /// Total time spent running the tasks of the queue.
+    pub runtime: Duration,
+    /// Number of tasks polled from the queue.
+    pub tasks_run: u64,
+    /// Number of times the queue went from having no runnable task to having
+    /// some.
+    pub activations: u64,
+    /// Time the queue waited between becoming runnable and being picked by
+    /// the executor.
+    pub queue_delay: Histogram,
+}
+
+impl TaskQueueStats {
+    pub(crate) fn new(name: &str) -> Self {
+        TaskQueueStats {
+            name: name.into(),
+            runtime: Duration::ZERO,
+            tasks_run: 0,
+            activations: 0,
+            queue_delay: Histogram::new(),
+        }
+    }
+}
+
+/// Number of buckets of a [`Histogram`]. The last one holds every sample of
+/// 2^20 microseconds (about a second) or more.
+const BUCKETS: usize = 22;
+
+/// A histogram of durations with power of two buckets: bucket `i` counts
+/// samples shorter than `2^i` microseconds, and longer than the bound of
+/// bucket `i - 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub(crate) fn new() -> Self {
+        Histogram {
+            buckets: [0; BUCKETS],
+            count: 0,
+            sum: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn record(&mut self, sample: Duration) {
+        let micros = sample.as_micros();
+        // Index of the smallest power of two above `micros`
+        let bucket = (u128::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.sum += sample;
+        self.max = self.max.max(sample);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+        }
+    }
+
+    /// Returns an upper bound of the `q`-quantile of the samples, with `q`
+    /// between 0 and 1: the bound of the bucket it falls in, or the largest
+    /// sample if it falls in the last bucket.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (upper_bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return upper_bound.unwrap_or(self.max).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Returns the upper bound of every bucket along with its number of
+    /// samples. The last bucket has no bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.buckets.iter().enumerate().map(|(i, &count)| {
+            let upper_bound = (i < BUCKETS - 1).then(|| Duration::from_micros(1 << i));
+            (upper_bound, count)
+        })
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
@@ -0,0 +1,154 @@
+use std::{thread, time::Duration};
+
+use crate::executor::{executor, spawn_local_into, yield_now};
+
+use super::{
+    local_executor::LocalExecutor,
+    queue_manager::QueueManager,
+    task_queue::{Latency, TaskQueue, TaskQueueHandle, MAX_SHARES},
+    task_queue_stats::Histogram,
+};
+
+#[test]
+fn vruntime_grows_inversely_to_shares() {
+    let heavy = TaskQueue::new(0, "heavy", 1000, Latency::NotImportant);
+    let light = TaskQueue::new(1, "light", 100, Latency::NotImportant);
+    let heavy_vruntime = heavy
+        .borrow_mut()
+        .account_vruntime(Duration::from_millis(1))
+        .unwrap();
+    let light_vruntime = light
+        .borrow_mut()
+        .account_vruntime(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(light_vruntime / heavy_vruntime, 10);
+}
+
+#[test]
+fn vruntime_overflow_is_reported() {
+    let queue = TaskQueue::new(0, "slow", 1, Latency::NotImportant);
+    assert!(queue
+        .borrow_mut()
+        .account_vruntime(Duration::from_secs(1 << 30))
+        .is_none());
+}
+
+#[test]
+#[should_panic(expected = "more than")]
+fn too_many_shares_are_rejected() {
+    LocalExecutor::default().create_task_queue("greedy", MAX_SHARES + 1, Latency::NotImportant);
+}
+
+#[test]
+#[should_panic(expected = "above zero")]
+fn zero_latency_is_rejected() {
+    LocalExecutor::default().create_task_queue("eager", 1000, Latency::Matters(Duration::ZERO));
+}
+
+#[test]
+fn preempt_timer_ignores_idle_queues() {
+    let mut manager = QueueManager::new();
+    let default = Duration::from_millis(100);
+    let queue = TaskQueue::new(
+        0,
+        "latency",
+        1000,
+        Latency::Matters(Duration::from_millis(1)),
+    );
+    manager.available_queues.insert(0, queue.clone());
+    assert_eq!(manager.preempt_timer_duration(default), default);
+
+    queue.borrow_mut().active = true;
+    assert_eq!(
+        manager.preempt_timer_duration(default),
+        Duration::from_millis(1)
+    );
+}
+
+#[test]
+fn active_queues_pop_smallest_vruntime() {
+    let mut manager = QueueManager::new();
+    let queues: Vec<_> = (0..3)
+        .map(|i| TaskQueue::new(i, "queue", 1000, Latency::NotImportant))
+        .collect();
+    queues[0].borrow_mut().vruntime = 30;
+    queues[1].borrow_mut().vruntime = 10;
+    queues[2].borrow_mut().vruntime = 20;
+    for queue in &queues {
+        manager
+            .available_queues
+            .insert(queue.borrow().index, queue.clone());
+        manager.maybe_activate_queue(queue.clone());
+    }
+    let order: Vec<_> = std::iter::from_fn(|| manager.active_queues.pop())
+        .map(|queue| queue.borrow().index)
+        .collect();
+    assert_eq!(order, vec![1, 2, 0]);
+}
+
+#[test]
+fn activated_queue_starts_at_last_vruntime() {
+    let mut manager = QueueManager::new();
+    let queue = TaskQueue::new(0, "idle", 1000, Latency::NotImportant);
+    manager.available_queues.insert(0, queue.clone());
+    manager.update_last_vruntime(Some(500));
+    manager.maybe_activate_queue(queue.clone());
+    assert_eq!(queue.borrow().vruntime, 500);
+
+    // An overflow resets every queue
+    manager.update_last_vruntime(None);
+    assert_eq!(queue.borrow().vruntime, 0);
+    assert_eq!(manager.last_vruntime, 0);
+}
+
+#[test]
+fn histogram_buckets_by_powers_of_two() {
+    let mut histogram = Histogram::new();
+    for micros in [0, 1, 3, 3, 100, 5_000_000] {
+        histogram.record(Duration::from_micros(micros));
+    }
+    assert_eq!(histogram.count(), 6);
+    assert_eq!(histogram.max(), Duration::from_secs(5));
+    let counts: Vec<_> = histogram.buckets().map(|(_, count)| count).collect();
+    assert_eq!(&counts[..3], &[1, 1, 2]);
+    assert_eq!(counts[7], 1);
+    assert_eq!(*counts.last().unwrap(), 1);
+    assert_eq!(histogram.quantile(0.5), Duration::from_micros(4));
+    assert_eq!(histogram.quantile(1.0), Duration::from_secs(5));
+}
+
+#[test]
+fn task_queue_stats_count_runs() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let tq = executor().create_task_queue("stats", 1000, Latency::NotImportant);
+        let stats = executor().task_queue_stats(tq).unwrap();
+        assert_eq!(stats.name, "stats");
+        assert_eq!((stats.tasks_run, stats.activations), (0, 0));
+
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                spawn_local_into(
+                    async {
+                        thread::sleep(Duration::from_millis(1));
+                        yield_now().await;
+                    },
+                    tq,
+                )
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let stats = executor().task_queue_stats(tq).unwrap();
+        // Every task is polled once more after yielding
+        assert_eq!(stats.tasks_run, 6);
+        assert!(stats.activations >= 1);
+        assert!(stats.queue_delay.count() >= stats.activations);
+        assert!(stats.runtime >= Duration::from_millis(3));
+        assert!(executor()
+            .task_queue_stats(TaskQueueHandle { index: 42 })
+            .is_none());
+    });
+}
@@ -5,3 +5,4 @@ pub mod pollable;
 pub mod reactor;
 pub mod sys;
 pub mod task;
+pub mod timer;
@@ -1,13 +1,20 @@
-use std::net::TcpListener;
+use std::{
+    net::{TcpListener, TcpStream},
+    thread,
+};
 
 use crate::{executor::local_executor::LocalExecutor, pollable::Async};
 
 #[test]
 fn simple_tcp_accept() {
     let local_ex = LocalExecutor::default();
-    let res = local_ex.run(async {
-        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 8080)).unwrap();
-        let (stream, addr) = listener.accept().await.unwrap();
+    local_ex.run(async {
+        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+        let addr = listener.get_ref().local_addr().unwrap();
+        // The executor parks in the ring until the client connects
+        let client = thread::spawn(move || TcpStream::connect(addr).unwrap());
+        let (_stream, addr) = listener.accept().await.unwrap();
         println!("Accepted client: {}", addr);
+        client.join().unwrap();
     });
 }
@@ -1,4 +1,4 @@
-use std::{io, rc::Rc};
+use std::{io, rc::Rc, time::Duration};
 
 use crate::executor::get_reactor;
 
@@ -20,8 +20,13 @@ impl Parker {
     }
 
     /// Blocks until notified and then goes back into sleeping state.
-    pub(crate) fn park(&self) -> io::Result<bool> {
-        todo!()
+    ///
+    /// The thread sleeps in the ring until at least one event completes, the
+    /// next timer of the executor is due or, if given, `timeout` elapses. The
+    /// wait is bounded by `io_uring_enter` itself, so nothing is left in the
+    /// ring when an event wakes the thread before the timeout.
+    pub(crate) fn park(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        self.inner.park(timeout)
     }
 
     /// Performs non-sleepable pool and install a preemption timeout into the
@@ -29,8 +34,8 @@ impl Parker {
     /// installing a preemption timer. Tasks executing in the CPU right after
     /// this will be able to check if the timer has elapsed and yield the
     /// CPU if that is the case.
-    pub(crate) fn poll_io(&self) -> io::Result<bool> {
-        self.inner.park()
+    pub(crate) fn poll_io(&self, timeout: Duration) -> io::Result<bool> {
+        self.inner.poll_io(timeout)
     }
 }
 
@@ -38,7 +43,21 @@ impl Parker {
 struct Inner {}
 
 impl Inner {
-    fn park(&self) -> io::Result<bool> {
-        get_reactor().react()
+    fn park(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let reactor = get_reactor();
+        let (woken, next_timer) = reactor.process_timers();
+        if woken > 0 {
+            // Some tasks can already run, so only poll for events
+            return reactor.react(Duration::ZERO);
+        }
+        let timeout = match (timeout, next_timer) {
+            (Some(timeout), Some(next_timer)) => Some(timeout.min(next_timer)),
+            (timeout, next_timer) => timeout.or(next_timer),
+        };
+        reactor.sleep(timeout)
+    }
+
+    fn poll_io(&self, timeout: Duration) -> io::Result<bool> {
+        get_reactor().react(timeout)
     }
 }
@@ -1,10 +1,17 @@
-use std::{io, os::fd::RawFd};
+use std::{
+    cell::RefCell,
+    io,
+    os::fd::RawFd,
+    task::Waker,
+    time::{Duration, Instant},
+};
 
 use nix::fcntl::{self, fcntl, FcntlArg, OFlag};
 
 use crate::{
     executor::executor,
     sys::{self, source::Source, SourceType},
+    timer::wheel::TimerWheel,
 };
 
 /// The reactor.
@@ -17,12 +24,16 @@ use crate::{
 
 pub(crate) struct Reactor {
     pub(crate) sys: sys::Reactor,
+    pub(crate) timers: RefCell<TimerWheel>,
 }
 
 impl Reactor {
-    pub(crate) fn new(ring_depth: usize) -> Reactor {
-        let sys = sys::Reactor::new(ring_depth);
-        Self { sys }
+    pub(crate) fn new(ring_depth: usize) -> io::Result<Reactor> {
+        let sys = sys::Reactor::new(ring_depth)?;
+        Ok(Self {
+            sys,
+            timers: RefCell::new(TimerWheel::new(Instant::now())),
+        })
     }
 
     fn new_source(&self, raw: RawFd, stype: SourceType) -> Source {
@@ -35,8 +46,50 @@ impl Reactor {
         self.new_source(raw, SourceType::PollableFd)
     }
 
-    pub fn react(&self) -> io::Result<bool> {
-        self.sys.wait();
+    pub fn react(&self, preempt_timer: Duration) -> io::Result<bool> {
+        self.sys.wait(preempt_timer)?;
+        self.process_timers();
+        Ok(true)
+    }
+
+    /// Blocks until an event completes or `timeout` elapses, then fires the
+    /// timers that expired.
+    pub fn sleep(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        self.sys.sleep(timeout)?;
+        self.process_timers();
         Ok(true)
     }
+
+    /// Registers a timer that wakes `waker` once `deadline` passes.
+    pub(crate) fn insert_timer(&self, deadline: Instant, waker: Waker) -> u64 {
+        self.timers.borrow_mut().insert(deadline, waker)
+    }
+
+    pub(crate) fn set_timer_waker(&self, id: u64, waker: &Waker) {
+        self.timers.borrow_mut().set_waker(id, waker)
+    }
+
+    pub(crate) fn remove_timer(&self, id: u64) {
+        self.timers.borrow_mut().remove(id)
+    }
+
+    /// Wakes the tasks whose timers expired. Returns how many were woken,
+    /// along with the time until the timers need to be processed again.
+    pub(crate) fn process_timers(&self) -> (usize, Option<Duration>) {
+        let now = Instant::now();
+        let (expired, next_timer) = {
+            let mut timers = self.timers.borrow_mut();
+            (timers.expire(now), timers.next_timeout(now))
+        };
+        // Wake outside of the borrow, since waking may drop other timers
+        let woken = expired.len();
+        for waker in expired {
+            waker.wake();
+        }
+        (woken, next_timer)
+    }
+
+    pub(crate) fn need_preempt(&self) -> bool {
+        self.sys.need_preempt()
+    }
 }
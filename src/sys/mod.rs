@@ -1,8 +1,37 @@
+mod notifier;
 pub mod source;
+mod topology;
 mod uring;
-pub(crate) use self::{source::*, uring::*};
+pub(crate) use self::{notifier::*, source::*, topology::*, uring::*};
+
+use std::{fmt, time::Duration};
 
 #[derive(Debug)]
 pub(crate) enum SourceType {
     PollableFd,
+    /// A timeout submitted to the ring, which completes with `ETIME` once
+    /// the duration elapses.
+    Timeout(TimeSpec),
+}
+
+/// The duration of a timeout. The kernel reads it when the sqe is submitted,
+/// so it's kept in the source until then.
+pub(crate) struct TimeSpec(uring_sys::__kernel_timespec);
+
+impl From<Duration> for TimeSpec {
+    fn from(duration: Duration) -> Self {
+        TimeSpec(uring_sys::__kernel_timespec {
+            tv_sec: duration.as_secs() as i64,
+            tv_nsec: duration.subsec_nanos() as _,
+        })
+    }
+}
+
+impl fmt::Debug for TimeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeSpec")
+            .field("tv_sec", &self.0.tv_sec)
+            .field("tv_nsec", &self.0.tv_nsec)
+            .finish()
+    }
 }
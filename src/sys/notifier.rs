@@ -0,0 +1,179 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+    task::Waker,
+};
+
+use nix::{
+    errno::Errno,
+    sys::eventfd::{eventfd, EfdFlags},
+};
+
+/// A request sent to an executor by a thread that doesn't own the task.
+#[derive(Debug)]
+pub(crate) enum RemoteWake {
+    /// Wakes the task and then drops the waker.
+    Wake(Waker),
+    /// Only drops the waker.
+    Drop(Waker),
+}
+
+#[derive(Debug)]
+struct Node {
+    request: RemoteWake,
+    next: *mut Node,
+}
+
+/// Lets other threads wake the tasks of an executor.
+///
+/// Wakers that are invoked from another thread can't touch the task since its
+/// state isn't synchronized. Instead, they push a request onto `inbox`, a
+/// lock-free stack that the executor drains on its own thread. The first
+/// request pushed onto an empty inbox also writes to `eventfd`, which the
+/// reactor polls so that a parked executor wakes up. Once the executor is
+/// gone, `inbox` is closed and requests are handed back to the sender.
+#[derive(Debug)]
+pub(crate) struct RemoteNotifier {
+    eventfd: OwnedFd,
+    inbox: AtomicPtr<Node>,
+}
+
+// The inbox only ever hands its `Waker`s, which are `Send`, to the thread that
+// drains it.
+unsafe impl Send for RemoteNotifier {}
+unsafe impl Sync for RemoteNotifier {}
+
+/// Value of `inbox` once the executor is gone. Never a valid `Node`.
+fn closed() -> *mut Node {
+    NonNull::dangling().as_ptr()
+}
+
+impl RemoteNotifier {
+    pub(crate) fn new() -> io::Result<Self> {
+        let eventfd = eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+        Ok(RemoteNotifier {
+            eventfd,
+            inbox: AtomicPtr::new(ptr::null_mut()),
+        })
+    }
+
+    pub(crate) fn eventfd(&self) -> RawFd {
+        self.eventfd.as_raw_fd()
+    }
+
+    /// Sends `request` to the executor. Safe to call from any thread.
+    ///
+    /// Gives `request` back if the executor is gone.
+    pub(crate) fn notify(&self, request: RemoteWake) -> Result<(), RemoteWake> {
+        let node = Box::into_raw(Box::new(Node {
+            request,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.inbox.load(Ordering::Relaxed);
+        loop {
+            if head == closed() {
+                return Err(unsafe { Box::from_raw(node) }.request);
+            }
+            unsafe { (*node).next = head };
+            match self
+                .inbox
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+
+        // Whoever fills an empty inbox is responsible for waking the executor.
+        // Later requests will be seen when it drains the inbox.
+        if head.is_null() {
+            let _ = nix::unistd::write(self.eventfd(), &1u64.to_ne_bytes());
+        }
+        Ok(())
+    }
+
+    /// Wakes the executor up without sending it a request, so that it goes
+    /// around its loop once more.
+    pub(crate) fn interrupt(&self) {
+        let _ = nix::unistd::write(self.eventfd(), &1u64.to_ne_bytes());
+    }
+
+    /// Returns true if there are requests that weren't processed yet.
+    pub(crate) fn has_pending(&self) -> bool {
+        let head = self.inbox.load(Ordering::Acquire);
+        !head.is_null() && head != closed()
+    }
+
+    /// Resets the eventfd after the reactor saw it become readable.
+    pub(crate) fn reset(&self) {
+        let mut buf = [0u8; 8];
+        match nix::unistd::read(self.eventfd(), &mut buf) {
+            Ok(_) | Err(Errno::EAGAIN) => {}
+            Err(err) => panic!("Failed to read from the eventfd: {}", err),
+        }
+    }
+
+    /// Processes every pending request in the order it was sent. Must be
+    /// called from the thread of the executor that owns this notifier.
+    ///
+    /// Returns true if there was any request.
+    pub(crate) fn process_remote_wakes(&self) -> bool {
+        let node = self.inbox.swap(ptr::null_mut(), Ordering::Acquire);
+        if node.is_null() {
+            return false;
+        }
+        for request in Self::take_requests(node) {
+            match request {
+                RemoteWake::Wake(waker) => waker.wake(),
+                RemoteWake::Drop(waker) => drop(waker),
+            }
+        }
+        true
+    }
+
+    /// Closes the inbox once the executor is gone, so that later requests
+    /// are handed back to their senders. Must be called from the thread of
+    /// the executor that owns this notifier.
+    ///
+    /// The requests that were still pending are dropped.
+    pub(crate) fn close(&self) {
+        let node = self.inbox.swap(closed(), Ordering::Acquire);
+        if !node.is_null() && node != closed() {
+            drop(Self::take_requests(node));
+        }
+    }
+
+    /// Unlinks the requests of the stack that starts at `node`, in the order
+    /// they were sent.
+    fn take_requests(mut node: *mut Node) -> Vec<RemoteWake> {
+        // The inbox is a stack, so reverse it to handle requests in order
+        let mut requests = Vec::new();
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            requests.push(boxed.request);
+        }
+        requests.reverse();
+        requests
+    }
+}
+
+impl Drop for RemoteNotifier {
+    fn drop(&mut self) {
+        // Nobody can run these tasks anymore, so their wakers are leaked
+        // rather than dropped on a thread that doesn't own them.
+        let mut node = *self.inbox.get_mut();
+        if node == closed() {
+            return;
+        }
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            match boxed.request {
+                RemoteWake::Wake(waker) | RemoteWake::Drop(waker) => std::mem::forget(waker),
+            }
+        }
+    }
+}
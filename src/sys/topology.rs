@@ -0,0 +1,88 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Where a CPU sits in the machine's topology.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct CpuLocation {
+    pub(crate) numa_node: usize,
+    pub(crate) package: usize,
+    /// Id of the physical core. Only unique within a package, and shared by
+    /// the hyperthreads of that core.
+    pub(crate) core: usize,
+    pub(crate) cpu: usize,
+}
+
+/// Reads the topology of the online CPUs of this machine.
+pub(crate) fn cpu_topology() -> io::Result<Vec<CpuLocation>> {
+    read_topology(Path::new("/sys/devices/system/cpu"))
+}
+
+/// Reads the topology of the online CPUs from a sysfs tree laid out like
+/// `/sys/devices/system/cpu`.
+pub(crate) fn read_topology(sysfs: &Path) -> io::Result<Vec<CpuLocation>> {
+    let online = fs::read_to_string(sysfs.join("online"))?;
+    parse_cpu_list(&online)?
+        .into_iter()
+        .map(|cpu| {
+            let cpu_dir = sysfs.join(format!("cpu{}", cpu));
+            let topology = cpu_dir.join("topology");
+            Ok(CpuLocation {
+                numa_node: numa_node(&cpu_dir)?,
+                package: read_package_id(topology.join("physical_package_id"))?,
+                core: read_id(topology.join("core_id"))?,
+                cpu,
+            })
+        })
+        .collect()
+}
+
+/// Parses a list of CPUs in the kernel's format, such as `0-3,6,8-9`.
+pub(crate) fn parse_cpu_list(list: &str) -> io::Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(parse_id(first)?..=parse_id(last)?),
+            None => cpus.push(parse_id(range)?),
+        }
+    }
+    Ok(cpus)
+}
+
+/// The NUMA node of a CPU is only exposed as a `nodeN` link in its
+/// directory. Machines without NUMA support have a single node.
+fn numa_node(cpu_dir: &Path) -> io::Result<usize> {
+    for entry in fs::read_dir(cpu_dir)? {
+        let name = entry?.file_name();
+        if let Some(node) = name.to_str().and_then(|name| name.strip_prefix("node")) {
+            if let Ok(node) = node.parse() {
+                return Ok(node);
+            }
+        }
+    }
+    Ok(0)
+}
+
+fn read_id(path: PathBuf) -> io::Result<usize> {
+    parse_id(&fs::read_to_string(path)?)
+}
+
+/// Some VMs and ARM systems report the package of their CPUs as -1, which is
+/// read as package 0.
+fn read_package_id(path: PathBuf) -> io::Result<usize> {
+    let id = fs::read_to_string(path)?;
+    match id.trim().parse::<i64>() {
+        Ok(package) if package < 0 => Ok(0),
+        _ => parse_id(&id),
+    }
+}
+
+fn parse_id(id: &str) -> io::Result<usize> {
+    id.trim().parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid CPU topology id: {:?}", id),
+        )
+    })
+}
@@ -1,15 +1,24 @@
 use std::{
-    cell::{RefCell, UnsafeCell},
+    cell::{Cell, RefCell, UnsafeCell},
     collections::{HashMap, VecDeque},
     io,
     os::fd::RawFd,
     pin::Pin,
     rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-use iou::sqe::PollFlags;
+use iou::sqe::{PollFlags, TimeoutFlags};
 
-use super::source::{InnerSource, Source};
+use super::{
+    notifier::RemoteNotifier,
+    source::{InnerSource, Source},
+    SourceType,
+};
 
 #[derive(Debug)]
 pub(crate) struct UringDescriptor {
@@ -21,6 +30,11 @@ pub(crate) struct UringDescriptor {
 #[derive(Debug)]
 enum UringOpDescriptor {
     PollAdd(PollFlags),
+    /// Points to the timespec of a `SourceType::Timeout` source, which is
+    /// kept alive by the source map until the timeout completes.
+    Timeout(*const uring_sys::__kernel_timespec),
+    /// Cancels the timeout submitted with the given user_data.
+    TimeoutRemove(u64),
 }
 
 #[derive(Debug)]
@@ -99,9 +113,12 @@ pub(crate) trait UringCommon {
 }
 
 #[derive(Debug)]
-struct SleepableRing {
+pub(crate) struct SleepableRing {
     ring: iou::IoUring,
-    in_kernel: usize,
+    pub(crate) in_kernel: usize,
+    // Whether `io_uring_enter` takes a timeout, otherwise sleeps are bounded
+    // by a timeout sqe
+    pub(crate) enter_timeout: bool,
     submission_queue: ReactorQueue,
     name: &'static str,
     source_map: Rc<RefCell<SourceMap>>,
@@ -142,7 +159,81 @@ impl UringCommon for SleepableRing {
     }
 }
 
+// `IORING_FEAT_EXT_ARG`, `IORING_ENTER_EXT_ARG` and
+// `struct io_uring_getevents_arg` (Linux 5.11), which our liburing predates
+const IORING_FEAT_EXT_ARG: u32 = 1 << 8;
+const IORING_ENTER_EXT_ARG: u32 = 1 << 3;
+
+#[repr(C)]
+struct GetEventsArg {
+    sigmask: u64,
+    sigmask_sz: u32,
+    pad: u32,
+    ts: u64,
+}
+
 impl SleepableRing {
+    /// Submits the pending sqes and blocks in `io_uring_enter` until at least
+    /// one cqe is available or `timeout` elapses.
+    ///
+    /// liburing bounds the wait with a timeout sqe, which stays in the kernel
+    /// when something else wakes the ring first. The timeout is passed to
+    /// `io_uring_enter` itself instead, so nothing outlives the sleep, unless
+    /// the kernel is older than 5.11.
+    fn sleep(&mut self, timeout: Option<Duration>) -> io::Result<usize> {
+        let res = match timeout {
+            Some(timeout) if self.enter_timeout => self
+                .ring
+                .submit_sqes()
+                .and_then(|submitted| self.wait_with_timeout(timeout).map(|_| submitted)),
+            Some(timeout) => self.ring.submit_sqes_and_wait_with_timeout(1, timeout),
+            None => self.ring.submit_sqes_and_wait(1),
+        };
+        // The kernel only fails with EINTR if nothing was submitted, so a
+        // signal is just one more reason to wake up.
+        let submitted = match res {
+            Ok(submitted) => submitted as usize,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => 0,
+            Err(err) => return Err(err),
+        };
+        self.in_kernel += submitted;
+        Ok(submitted)
+    }
+
+    /// Waits for a cqe without submitting anything, for at most `timeout`.
+    fn wait_with_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        let ts = uring_sys::__kernel_timespec {
+            tv_sec: timeout.as_secs() as i64,
+            tv_nsec: timeout.subsec_nanos() as _,
+        };
+        let arg = GetEventsArg {
+            sigmask: 0,
+            sigmask_sz: 0,
+            pad: 0,
+            ts: &ts as *const _ as u64,
+        };
+        let flags = uring_sys::IORING_ENTER_GETEVENTS | IORING_ENTER_EXT_ARG;
+        let res = unsafe {
+            nix::libc::syscall(
+                nix::libc::SYS_io_uring_enter,
+                self.ring.raw().ring_fd,
+                0,
+                1,
+                flags,
+                &arg as *const GetEventsArg,
+                std::mem::size_of::<GetEventsArg>(),
+            )
+        };
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            // The kernel reports an elapsed timeout as ETIME
+            if err.raw_os_error() != Some(nix::libc::ETIME) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
     fn new(
         size: usize,
         name: &'static str,
@@ -151,6 +242,7 @@ impl SleepableRing {
         Ok(SleepableRing {
             ring: iou::IoUring::new(size as _)?,
             in_kernel: 0,
+            enter_timeout: ring_features()? & IORING_FEAT_EXT_ARG != 0,
             submission_queue: UringQueueState::with_capacity(size * 4),
             name,
             source_map,
@@ -158,6 +250,22 @@ impl SleepableRing {
     }
 }
 
+/// Returns the features the kernel advertises when a ring is set up.
+fn ring_features() -> io::Result<u32> {
+    // `iou` doesn't keep the parameters its rings are set up with, so a
+    // throwaway ring is set up to read them
+    unsafe {
+        let mut params: uring_sys::io_uring_params = std::mem::zeroed();
+        let mut ring = std::mem::MaybeUninit::uninit();
+        let res = uring_sys::io_uring_queue_init_params(1, ring.as_mut_ptr(), &mut params);
+        if res < 0 {
+            return Err(io::Error::from_raw_os_error(-res));
+        }
+        uring_sys::io_uring_queue_exit(ring.as_mut_ptr());
+        Ok(params.features)
+    }
+}
+
 fn fill_sqe(sqe: &mut iou::SQE<'_>, op: &UringDescriptor) {
     let mut user_data = op.user_data;
     unsafe {
@@ -165,27 +273,139 @@ fn fill_sqe(sqe: &mut iou::SQE<'_>, op: &UringDescriptor) {
             UringOpDescriptor::PollAdd(flags) => {
                 sqe.prep_poll_add(op.fd, flags);
             }
+            UringOpDescriptor::Timeout(timespec) => {
+                sqe.prep_timeout(&*timespec, 0, TimeoutFlags::empty());
+            }
+            UringOpDescriptor::TimeoutRemove(timeout_id) => {
+                sqe.prep_timeout_remove(timeout_id);
+                // The removal itself has no source to complete
+                user_data = 0;
+            }
         }
         sqe.set_user_data(user_data);
     }
 }
 
+/// user_data of the preemption timer. There is at most one in the kernel at
+/// any time.
+const PREEMPT_TIMER_ID: u64 = 1;
+
+/// A ring that only ever holds the preemption timer.
+///
+/// Completions on this ring are only consumed when the timer is rearmed, so a
+/// non-empty completion queue means that the timer fired. The head and tail of
+/// the completion queue live in memory shared with the kernel, which makes
+/// checking for preemption as cheap as comparing two integers.
+#[derive(Debug)]
+struct PreemptTimer {
+    ring: iou::IoUring,
+    armed: bool,
+}
+
+impl PreemptTimer {
+    fn new() -> io::Result<Self> {
+        Ok(PreemptTimer {
+            ring: iou::IoUring::new(4)?,
+            armed: false,
+        })
+    }
+
+    fn fired(&self) -> bool {
+        let cq = &self.ring.raw().cq;
+        unsafe {
+            let head = &*(cq.khead as *const AtomicU32);
+            let tail = &*(cq.ktail as *const AtomicU32);
+            head.load(Ordering::Relaxed) != tail.load(Ordering::Acquire)
+        }
+    }
+
+    /// Replaces the timer in the kernel with one that fires after `duration`.
+    /// A zero `duration` only removes the current timer.
+    fn rearm(&mut self, duration: Duration) -> io::Result<()> {
+        if self.armed && !self.fired() {
+            let mut sqe = self.ring.prepare_sqe().unwrap();
+            unsafe {
+                sqe.prep_timeout_remove(PREEMPT_TIMER_ID);
+                sqe.set_user_data(0);
+            }
+            // Both the removal and the canceled timer complete right away
+            self.ring.submit_sqes_and_wait(2)?;
+        }
+        while self.ring.peek_for_cqe().is_some() {}
+        self.armed = false;
+
+        if duration.is_zero() {
+            return Ok(());
+        }
+
+        // The kernel copies the timespec when the sqe is submitted, so it only
+        // needs to outlive `submit_sqes`.
+        let timespec = uring_sys::__kernel_timespec {
+            tv_sec: duration.as_secs() as i64,
+            tv_nsec: duration.subsec_nanos() as _,
+        };
+        let mut sqe = self.ring.prepare_sqe().unwrap();
+        unsafe {
+            sqe.prep_timeout(&timespec, 0, TimeoutFlags::empty());
+            sqe.set_user_data(PREEMPT_TIMER_ID);
+        }
+        self.ring.submit_sqes()?;
+        self.armed = true;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Reactor {
-    main_ring: RefCell<SleepableRing>,
+    pub(crate) main_ring: RefCell<SleepableRing>,
+    preempt_timer: RefCell<PreemptTimer>,
     source_map: Rc<RefCell<SourceMap>>,
+    notifier: Arc<RemoteNotifier>,
+    // Source for the notifier's eventfd, and whether a poll on it is in the
+    // ring
+    notifier_source: Source,
+    notifier_armed: Cell<bool>,
 }
 
 impl Reactor {
-    pub(crate) fn new(ring_depth: usize) -> Reactor {
+    pub(crate) fn new(ring_depth: usize) -> io::Result<Reactor> {
         let source_map = Rc::new(RefCell::new(SourceMap::new()));
-        let main_ring = SleepableRing::new(ring_depth, "main", source_map.clone()).unwrap();
-        Reactor {
+        let main_ring = SleepableRing::new(ring_depth, "main", source_map.clone())?;
+        let notifier = Arc::new(RemoteNotifier::new()?);
+        let notifier_source = Source::new(notifier.eventfd(), SourceType::PollableFd, None);
+        Ok(Reactor {
             main_ring: RefCell::new(main_ring),
+            preempt_timer: RefCell::new(PreemptTimer::new()?),
             source_map,
+            notifier,
+            notifier_source,
+            notifier_armed: Cell::new(false),
+        })
+    }
+
+    pub(crate) fn notifier(&self) -> &Arc<RemoteNotifier> {
+        &self.notifier
+    }
+
+    /// Makes sure the ring polls the notifier's eventfd, so that remote wakes
+    /// interrupt `sleep`.
+    fn rearm_notifier(&self) {
+        if self.notifier_source.take_result().is_some() {
+            self.notifier.reset();
+            self.notifier_armed.set(false);
+        }
+        if !self.notifier_armed.get() {
+            self.interest(&self.notifier_source, true, false);
+            self.notifier_armed.set(true);
         }
     }
 
+    /// Returns true if the preemption timer installed by the last call to
+    /// `wait` has fired. This doesn't issue any syscall.
+    pub(crate) fn need_preempt(&self) -> bool {
+        self.preempt_timer.borrow().fired()
+    }
+
     pub(crate) fn interest(&self, source: &Source, read: bool, write: bool) {
         let mut flags = common_flags();
         if read {
@@ -203,11 +423,62 @@ impl Reactor {
         );
     }
 
-    pub(crate) fn wait(&self) {
+    /// Submits the timeout of `source`, which must be a `SourceType::Timeout`
+    /// source. Its result is set to `ETIME` when the timeout fires, or to
+    /// `ECANCELED` if it's canceled first.
+    ///
+    /// Returns the id to pass to `cancel_timeout`.
+    pub(crate) fn timeout(&self, source: &Source) -> u64 {
+        let timespec = match &source.inner.borrow().source_type {
+            SourceType::Timeout(timespec) => &timespec.0 as *const _,
+            source_type => panic!("Can't submit a timeout for {:?}", source_type),
+        };
+        queue_request_into_ring(
+            &mut *self.main_ring.borrow_mut(),
+            source,
+            UringOpDescriptor::Timeout(timespec),
+            &mut self.source_map.clone(),
+        )
+    }
+
+    /// Cancels a timeout submitted by `timeout`. Does nothing if it already
+    /// fired.
+    pub(crate) fn cancel_timeout(&self, id: u64) {
+        let q = self.main_ring.borrow_mut().submission_queue();
+        q.borrow_mut().submissions.push_back(UringDescriptor {
+            args: UringOpDescriptor::TimeoutRemove(id),
+            fd: -1,
+            user_data: 0,
+        });
+    }
+
+    pub(crate) fn wait(&self, preempt_timer: Duration) -> io::Result<()> {
+        self.main_ring.borrow_mut().consume_completion_queue();
+        self.rearm_notifier();
+
         let mut main_ring = self.main_ring.borrow_mut();
+        main_ring.consume_submission_queue()?;
+        drop(main_ring);
 
-        main_ring.consume_completion_queue();
-        main_ring.consume_submission_queue().unwrap();
+        self.preempt_timer.borrow_mut().rearm(preempt_timer)
+    }
+
+    /// Blocks the thread until an event completes or `timeout` elapses.
+    pub(crate) fn sleep(&self, timeout: Option<Duration>) -> io::Result<()> {
+        // Nothing runs until we wake up, so there is nothing to preempt
+        self.preempt_timer.borrow_mut().rearm(Duration::ZERO)?;
+        self.rearm_notifier();
+
+        let mut main_ring = self.main_ring.borrow_mut();
+        main_ring.consume_submission_queue()?;
+        // A request pushed after the inbox was last drained may have had its
+        // eventfd write consumed by the reset above, so the eventfd can't be
+        // relied on to wake us up for it.
+        if main_ring.consume_completion_queue() == 0 && !self.notifier.has_pending() {
+            main_ring.sleep(timeout)?;
+            main_ring.consume_completion_queue();
+        }
+        Ok(())
     }
 }
 
@@ -230,7 +501,7 @@ fn queue_request_into_ring(
     source: &Source,
     descriptor: UringOpDescriptor,
     source_map: &mut Rc<RefCell<SourceMap>>,
-) {
+) -> u64 {
     let q = ring.submission_queue();
 
     let id = source_map.borrow_mut().add_source(source, Rc::clone(&q));
@@ -242,6 +513,7 @@ fn queue_request_into_ring(
         fd: source.raw(),
         user_data: id,
     });
+    id
 }
 
 #[derive(Debug)]
@@ -282,6 +554,10 @@ fn process_one_event(cqe: Option<iou::CQE>, source_map: Rc<RefCell<SourceMap>>)
         if value.user_data() == 0 {
             return Some(false);
         }
+        // Neither is the timeout sqe bounding a sleep on older kernels.
+        if value.user_data() == uring_sys::LIBURING_UDATA_TIMEOUT {
+            return Some(false);
+        }
 
         let src = source_map.borrow_mut().consume_source(value.user_data());
 
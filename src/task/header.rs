@@ -1,12 +1,23 @@
 use core::fmt;
 use std::{
-    sync::atomic::{AtomicI16, Ordering},
+    any::Any,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicI16, Ordering},
+        Arc,
+    },
     task::Waker,
+    time::Instant,
 };
 
+use crate::sys::RemoteNotifier;
+
 use super::{
+    live_tasks::LiveTasks,
     raw::TaskVTable,
     state::{CLOSED, COMPLETED},
+    task_id::TaskId,
+    task_local::TaskLocals,
 };
 
 pub(crate) struct Header {
@@ -14,6 +25,29 @@ pub(crate) struct Header {
 
     pub(crate) executor_id: usize,
 
+    /// Used by wakers invoked from other threads to hand the task back to
+    /// the executor that owns it.
+    pub(crate) notifier: Arc<RemoteNotifier>,
+
+    pub(crate) id: TaskId,
+
+    /// The name given to the task when it was spawned, if any.
+    pub(crate) name: Option<String>,
+
+    /// Index of the `TaskQueue` the task was spawned into.
+    pub(crate) task_queue: usize,
+
+    /// Number of times the future was polled, and when it last was.
+    pub(crate) polls: u64,
+    pub(crate) last_poll: Option<Instant>,
+
+    /// The values of the task-local keys set by the scopes of the task.
+    pub(crate) locals: TaskLocals,
+
+    /// The live tasks of the executor, which the task leaves when it's
+    /// destroyed.
+    pub(crate) live_tasks: Rc<LiveTasks>,
+
     /// Current reference count of the task.
     pub(crate) references: AtomicI16,
 
@@ -31,6 +65,10 @@ pub(crate) struct Header {
     ///
     /// This waker needs to be woken up once the task completes or is closed.
     pub(crate) awaiter: Option<Waker>,
+
+    /// The payload of the panic that closed the task, until the `JoinHandle`
+    /// takes it.
+    pub(crate) panic: Option<Box<dyn Any + Send>>,
 }
 
 impl Header {
@@ -82,6 +120,8 @@ impl fmt::Debug for Header {
         let refcount = self.references.load(Ordering::Relaxed);
         f.debug_struct("Header")
             .field("ptr", &(self as *const Self))
+            .field("id", &self.id)
+            .field("name", &self.name)
             .field("refcount", &refcount)
             .finish()
     }
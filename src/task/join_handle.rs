@@ -1,16 +1,21 @@
-use std::{future::Future, marker::PhantomData, ptr::NonNull, sync::atomic::Ordering, task::Poll};
+use std::{
+    any::Any, fmt, future::Future, marker::PhantomData, panic, ptr::NonNull,
+    sync::atomic::Ordering, task::Poll,
+};
 
 use super::{
     header::Header,
     state::{CLOSED, COMPLETED, HANDLE, RUNNING, SCHEDULED},
+    task_id::TaskId,
 };
 
 /// A handle that awaits the result of a task.
 ///
-/// This type is a future that resolves to an `Option<R>` where:
+/// This type is a future that resolves to a `Result<R, JoinError>` where:
 ///
-/// * `None` indicates the task has panicked or was canceled.
-/// * `Some(result)` indicates the task has completed with `result` of type `R`.
+/// * `Err(JoinError::Panicked(payload))` indicates the task has panicked.
+/// * `Err(JoinError::Cancelled)` indicates the task was canceled.
+/// * `Ok(result)` indicates the task has completed with `result` of type `R`.
 pub struct JoinHandle<R> {
     /// A raw task pointer.
     pub(crate) raw_task: NonNull<()>,
@@ -19,8 +24,170 @@ pub struct JoinHandle<R> {
     pub(crate) _marker: PhantomData<R>,
 }
 
+/// The reason why a task didn't complete.
+#[derive(Debug)]
+pub enum JoinError {
+    /// The task was canceled before it completed.
+    Cancelled,
+    /// The task panicked. Holds the payload of the panic.
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl JoinError {
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, JoinError::Cancelled)
+    }
+
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JoinError::Panicked(_))
+    }
+
+    /// Returns the payload of the panic, or gives the error back if the task
+    /// was canceled.
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send>, JoinError> {
+        match self {
+            JoinError::Panicked(payload) => Ok(payload),
+            err => Err(err),
+        }
+    }
+
+    /// Returns the payload of the panic.
+    ///
+    /// Panics if the task was canceled.
+    pub fn into_panic(self) -> Box<dyn Any + Send> {
+        self.try_into_panic()
+            .expect("`JoinError::into_panic` called on a cancelled task")
+    }
+
+    /// Raises the panic of the task again on the current thread, with its
+    /// original payload. A canceled task raises a new panic.
+    pub fn resume_unwind(self) -> ! {
+        match self {
+            JoinError::Panicked(payload) => panic::resume_unwind(payload),
+            JoinError::Cancelled => panic!("{}", JoinError::Cancelled),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Cancelled => f.write_str("task was cancelled"),
+            JoinError::Panicked(payload) => match panic_message(payload.as_ref()) {
+                Some(msg) => write!(f, "task panicked: {}", msg),
+                None => f.write_str("task panicked"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// Panics raised with a message carry a `&str` or a `String` payload.
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+impl<R> fmt::Debug for JoinHandle<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = self.raw_task.as_ptr() as *const Header;
+        f.debug_struct("JoinHandle")
+            .field("header", unsafe { &*header })
+            .finish()
+    }
+}
+
+// The output is never stored inline in the handle, so moving it is fine.
+impl<R> Unpin for JoinHandle<R> {}
+
+impl<R> JoinHandle<R> {
+    pub fn id(&self) -> TaskId {
+        unsafe { (*(self.raw_task.as_ptr() as *const Header)).id }
+    }
+
+    /// Returns the name the task was spawned with, if any.
+    pub fn name(&self) -> Option<&str> {
+        unsafe { (*(self.raw_task.as_ptr() as *const Header)).name.as_deref() }
+    }
+
+    /// Cancels the task without waiting for it.
+    ///
+    /// The future of the task is dropped by the executor the next time it
+    /// runs the task, or right after the current poll if the task is running.
+    /// Awaiting the handle then resolves to `Err(JoinError::Cancelled)`.
+    /// Does nothing if the task is already completed or closed.
+    pub fn abort(&self) {
+        let ptr = self.raw_task.as_ptr();
+        let header = ptr as *mut Header;
+
+        unsafe {
+            let state = (*header).state;
+
+            // If the task has been completed or closed, it can't be canceled.
+            if state & (COMPLETED | CLOSED) != 0 {
+                return;
+            }
+
+            if state & (SCHEDULED | RUNNING) == 0 {
+                // Nothing is going to run the task, so schedule it one more
+                // time so that its future gets dropped by the executor.
+                (*header).state = state | SCHEDULED | CLOSED;
+                ((*header).vtable.schedule)(ptr);
+            } else {
+                (*header).state = state | CLOSED;
+            }
+        }
+    }
+
+    /// Cancels the task and returns a future that resolves once its future
+    /// has been dropped.
+    ///
+    /// The future resolves to the output of the task if it had already
+    /// completed, to `Err(JoinError::Panicked(payload))` if it had panicked,
+    /// and to `Err(JoinError::Cancelled)` otherwise.
+    pub fn cancel(self) -> Cancel<R> {
+        self.abort();
+        Cancel { handle: self }
+    }
+
+    /// Lets the task run to completion without anything waiting for it.
+    ///
+    /// This is the same as dropping the handle, but makes the intent
+    /// explicit. The output of the task is dropped when it completes.
+    pub fn detach(self) {
+        drop(self);
+    }
+
+    /// Returns true if the task won't run anymore, either because it
+    /// completed, panicked, or was canceled and its future was dropped.
+    pub fn is_finished(&self) -> bool {
+        let state = unsafe { (*(self.raw_task.as_ptr() as *const Header)).state };
+        state & COMPLETED != 0 || (state & CLOSED != 0 && state & (SCHEDULED | RUNNING) == 0)
+    }
+}
+
+/// Future returned by [`JoinHandle::cancel`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Cancel<R> {
+    handle: JoinHandle<R>,
+}
+
+impl<R> Future for Cancel<R> {
+    type Output = Result<R, JoinError>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        std::pin::Pin::new(&mut self.handle).poll(cx)
+    }
+}
+
 impl<R> Future for JoinHandle<R> {
-    type Output = Option<R>;
+    type Output = Result<R, JoinError>;
 
     fn poll(
         self: std::pin::Pin<&mut Self>,
@@ -33,7 +200,7 @@ impl<R> Future for JoinHandle<R> {
         unsafe {
             let state = (*header).state;
 
-            // If the task has been closed, notify the awaiter and return `None`.
+            // If the task has been closed, notify the awaiter and return why.
             if state & CLOSED != 0 {
                 // If the task is scheduled or running, we need to wait until its future is
                 // dropped.
@@ -44,7 +211,10 @@ impl<R> Future for JoinHandle<R> {
                 }
 
                 (*header).notify(Some(cx.waker()));
-                return Poll::Ready(None);
+                return Poll::Ready(Err(match (*header).panic.take() {
+                    Some(payload) => JoinError::Panicked(payload),
+                    None => JoinError::Cancelled,
+                }));
             }
 
             if state & COMPLETED == 0 {
@@ -62,7 +232,7 @@ impl<R> Future for JoinHandle<R> {
 
             // Take the output from the task.
             let output = ((*header).vtable.get_output)(ptr) as *mut R;
-            Poll::Ready(Some(output.read()))
+            Poll::Ready(Ok(output.read()))
         }
     }
 }
@@ -116,7 +286,7 @@ impl<R> Drop for JoinHandle<R> {
                 if refs == 0 {
                     if state & CLOSED == 0 {
                         let refs = (*header).references.fetch_add(1, Ordering::Relaxed);
-                        assert_ne!(refs, i16::max_value());
+                        assert_ne!(refs, i16::MAX);
                         ((*header).vtable.schedule)(ptr);
                     } else {
                         ((*header).vtable.destroy)(ptr);
@@ -0,0 +1,104 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    ptr::NonNull,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use crate::executor::task_queue::TaskQueueHandle;
+
+use super::{
+    header::Header,
+    state::{CLOSED, COMPLETED, HANDLE, RUNNING, SCHEDULED},
+    task_id::TaskId,
+};
+
+/// The tasks of an executor that weren't destroyed yet.
+///
+/// Tasks add themselves when they are allocated and remove themselves when
+/// they are destroyed, so that their headers can be inspected at any time.
+#[derive(Debug, Default)]
+pub(crate) struct LiveTasks {
+    tasks: RefCell<BTreeMap<TaskId, NonNull<()>>>,
+}
+
+impl LiveTasks {
+    pub(crate) fn insert(&self, id: TaskId, ptr: NonNull<()>) {
+        self.tasks.borrow_mut().insert(id, ptr);
+    }
+
+    pub(crate) fn remove(&self, id: TaskId) {
+        self.tasks.borrow_mut().remove(&id);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.tasks.borrow().len()
+    }
+
+    /// Returns the state of every live task, ordered by id.
+    pub(crate) fn snapshot(&self) -> Vec<TaskInfo> {
+        let now = Instant::now();
+        self.tasks
+            .borrow()
+            .values()
+            .map(|ptr| {
+                // Tasks remove themselves before their header is dropped
+                let header = unsafe { &*(ptr.as_ptr() as *const Header) };
+                TaskInfo {
+                    id: header.id,
+                    name: header.name.clone(),
+                    task_queue: TaskQueueHandle {
+                        index: header.task_queue,
+                    },
+                    state: TaskState::from_bits(header.state),
+                    references: header.references.load(Ordering::Relaxed),
+                    polls: header.polls,
+                    since_last_poll: header.last_poll.map(|last_poll| now - last_poll),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A snapshot of a live task, as returned by
+/// [`LocalExecutor::dump_tasks`](crate::executor::local_executor::LocalExecutor::dump_tasks).
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: Option<String>,
+    /// The queue the task was spawned into.
+    pub task_queue: TaskQueueHandle,
+    pub state: TaskState,
+    /// Number of `Task`s and `Waker`s pointing to the task. The `JoinHandle`
+    /// isn't counted, see [`TaskState::handle`].
+    pub references: i16,
+    /// Number of times the future of the task was polled.
+    pub polls: u64,
+    /// Time elapsed since the future of the task was last polled, or `None`
+    /// if it never was.
+    pub since_last_poll: Option<Duration>,
+}
+
+/// The state bits of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskState {
+    pub scheduled: bool,
+    pub running: bool,
+    pub completed: bool,
+    pub closed: bool,
+    /// Whether the `JoinHandle` of the task still exists.
+    pub handle: bool,
+}
+
+impl TaskState {
+    fn from_bits(state: u8) -> TaskState {
+        TaskState {
+            scheduled: state & SCHEDULED != 0,
+            running: state & RUNNING != 0,
+            completed: state & COMPLETED != 0,
+            closed: state & CLOSED != 0,
+            handle: state & HANDLE != 0,
+        }
+    }
+}
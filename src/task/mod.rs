@@ -1,6 +1,10 @@
 pub mod header;
 pub mod join_handle;
+pub mod live_tasks;
 pub mod raw;
 pub(crate) mod state;
 pub(crate) mod task;
+pub mod task_id;
+pub mod task_local;
+mod task_local_test;
 pub(crate) mod utils;
@@ -2,16 +2,22 @@ use std::{
     alloc::{self, Layout},
     future::Future,
     mem::{self, ManuallyDrop},
+    panic::{self, AssertUnwindSafe},
     pin::Pin,
     ptr::NonNull,
     sync::atomic::{AtomicI16, Ordering},
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    time::Instant,
 };
 
+use crate::{executor, sys::RemoteWake};
+
 use super::{
     header::Header,
     state::{CLOSED, COMPLETED, HANDLE, RUNNING, SCHEDULED},
-    task::Task,
+    task::{Task, TaskMeta},
+    task_id::{set_current_task_id, TaskId},
+    task_local::{set_current_locals, TaskLocals},
     utils::extend,
 };
 
@@ -63,15 +69,25 @@ where
         Self::drop_waker,
     );
 
-    pub(crate) fn allocate(future: F, schedule: S, executor_id: usize) -> NonNull<()> {
+    pub(crate) fn allocate(future: F, schedule: S, meta: TaskMeta) -> NonNull<()> {
         let task_layout = Self::task_layout();
         unsafe {
             let raw_task = NonNull::new(alloc::alloc(task_layout.layout) as *mut ()).unwrap();
             let raw = Self::from_ptr(raw_task.as_ptr());
+            let id = TaskId::next();
+            meta.live_tasks.insert(id, raw_task);
             // Write the header as the first field of the task.
             (raw.header as *mut Header).write(Header {
                 state: SCHEDULED | HANDLE,
-                executor_id,
+                executor_id: meta.executor_id,
+                notifier: meta.notifier,
+                id,
+                name: meta.name,
+                task_queue: meta.task_queue,
+                polls: 0,
+                last_poll: None,
+                locals: TaskLocals::default(),
+                live_tasks: meta.live_tasks,
                 references: AtomicI16::new(0),
                 vtable: &TaskVTable {
                     schedule: Self::schedule,
@@ -82,6 +98,7 @@ where
                     run: Self::run,
                 },
                 awaiter: None,
+                panic: None,
             });
 
             // Write the schedule function as the third field of the task.
@@ -145,19 +162,31 @@ where
         let raw = Self::from_ptr(ptr);
         let task_layout = Self::task_layout();
 
+        (*raw.header).live_tasks.remove((*raw.header).id);
+
+        // Drop the header so that the notifier and the awaiter are released.
+        (raw.header as *mut Header).drop_in_place();
+
         // TODO: We should safeguard against dropping schedule because it
         // contains a closure
         alloc::dealloc(ptr as *mut u8, task_layout.layout);
     }
 
-    fn increment_references(header: &mut Header) {
-        let refs = header.references.fetch_add(1, Ordering::Relaxed);
-        assert_ne!(refs, i16::MAX, "Waker invariant broken: {:?}", header);
+    /// Takes the reference count rather than the header, since wakers on
+    /// other threads may only touch the count while the executor uses the
+    /// rest of the header.
+    fn increment_references(references: &AtomicI16) {
+        let refs = references.fetch_add(1, Ordering::Relaxed);
+        assert_ne!(
+            refs,
+            i16::MAX,
+            "Waker invariant broken: too many references"
+        );
     }
 
-    fn decrement_references(header: &mut Header) -> i16 {
-        let refs = header.references.fetch_sub(1, Ordering::Relaxed);
-        assert_ne!(refs, 0, "Waker invariant broken: {:?}", header);
+    fn decrement_references(references: &AtomicI16) -> i16 {
+        let refs = references.fetch_sub(1, Ordering::Relaxed);
+        assert_ne!(refs, 0, "Waker invariant broken: no references left");
         refs - 1
     }
 
@@ -170,7 +199,10 @@ where
         println!("Wake_by_ref");
         let raw = Self::from_ptr(ptr);
         if Self::thread_id() != Some(raw.my_executor_id()) {
-            todo!()
+            // The task's state can only be touched by its executor, so hand
+            // it a new reference to wake from its own thread.
+            Self::increment_references(&(*raw.header).references);
+            Self::notify_executor(ptr, RemoteWake::Wake);
         } else {
             let state = (*raw.header).state;
 
@@ -189,13 +221,32 @@ where
         }
     }
 
+    /// Hands one reference to the task over to the executor that owns it, to
+    /// be used from the executor's thread.
+    ///
+    /// If that executor is gone, the reference is dropped here instead.
+    unsafe fn notify_executor(ptr: *const (), request: fn(Waker) -> RemoteWake) {
+        let raw = Self::from_ptr(ptr);
+        let waker = Waker::from_raw(RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE));
+        if let Err(RemoteWake::Wake(waker) | RemoteWake::Drop(waker)) =
+            (*raw.header).notifier.notify(request(waker))
+        {
+            // Dropping the waker would come back here
+            mem::forget(waker);
+            // Nobody can run the task anymore, so there is nothing to wake.
+            // If this was the last reference, the task is leaked: its future
+            // can only be dropped on the executor's thread.
+            Self::decrement_references(&(*raw.header).references);
+        }
+    }
+
     /// Schedules a task for running.
     ///
     /// This function doesn't modify the state of the task. It only passes the
     /// task reference to its schedule function.
     unsafe fn schedule(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
-        Self::increment_references(&mut *(raw.header as *mut Header));
+        Self::increment_references(&(*raw.header).references);
 
         // Calling of schedule functions itself does not increment references,
         // if the schedule function has captured variables, increment references
@@ -218,14 +269,20 @@ where
     /// Clones a waker.
     unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
         let raw = Self::from_ptr(ptr);
-        Self::increment_references(&mut *(raw.header as *mut Header));
+        Self::increment_references(&(*raw.header).references);
         RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE)
     }
 
     /// Wakes a waker. Ptr is the raw task.
     unsafe fn wake(ptr: *const ()) {
-        Self::wake_by_ref(ptr);
-        Self::drop_waker(ptr);
+        let raw = Self::from_ptr(ptr);
+        if Self::thread_id() != Some(raw.my_executor_id()) {
+            // Hand our own reference over to the executor
+            Self::notify_executor(ptr, RemoteWake::Wake);
+        } else {
+            Self::wake_by_ref(ptr);
+            Self::drop_waker(ptr);
+        }
     }
 
     /// Drops a waker.
@@ -238,9 +295,11 @@ where
     unsafe fn drop_waker(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
         if Self::thread_id() != Some(raw.my_executor_id()) {
-            todo!()
+            // Dropping the last reference may destroy the task, which can
+            // only happen on the executor's thread.
+            Self::notify_executor(ptr, RemoteWake::Drop);
         } else {
-            let refs = Self::decrement_references(&mut *(raw.header as *mut Header));
+            let refs = Self::decrement_references(&(*raw.header).references);
 
             let state = (*raw.header).state;
 
@@ -265,6 +324,9 @@ where
 
     unsafe fn drop_future(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
+        // The destructors of the future see the task-local values of the task
+        let previous_locals = set_current_locals(&(*raw.header).locals);
+        let _guard = RestoreLocals(previous_locals);
         raw.future.drop_in_place();
     }
 
@@ -278,7 +340,7 @@ where
         let raw = Self::from_ptr(ptr);
 
         // Decrement the reference count.
-        let refs = Self::decrement_references(&mut *(raw.header as *mut Header));
+        let refs = Self::decrement_references(&(*raw.header).references);
 
         let state = (*raw.header).state;
 
@@ -311,6 +373,9 @@ where
             // Mark the task as unscheduled.
             (*(raw.header as *mut Header)).state &= !SCHEDULED;
 
+            // Notify the awaiter that the future has been dropped.
+            (*(raw.header as *mut Header)).notify(None);
+
             // Drop the task reference.
             Self::drop_task(ptr);
             return false;
@@ -323,8 +388,18 @@ where
         let waker = ManuallyDrop::new(Waker::from_raw(RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE)));
         let cx = &mut Context::from_waker(&waker);
 
-        // TODO: Guard
-        let poll = <F as Future>::poll(Pin::new_unchecked(&mut *raw.future), cx);
+        {
+            let header = &mut *(raw.header as *mut Header);
+            header.polls += 1;
+            header.last_poll = Some(Instant::now());
+        }
+        let previous_task = set_current_task_id(Some((*raw.header).id));
+        let previous_locals = set_current_locals(&(*raw.header).locals);
+        let poll = panic::catch_unwind(AssertUnwindSafe(|| {
+            <F as Future>::poll(Pin::new_unchecked(&mut *raw.future), cx)
+        }));
+        set_current_locals(previous_locals);
+        set_current_task_id(previous_task);
 
         // state could be updated after the poll
         state = (*raw.header).state;
@@ -333,7 +408,27 @@ where
         // if the task is not complete and not closed.
         let mut ret = false;
         match poll {
-            Poll::Ready(out) => {
+            Err(payload) => {
+                // The future may be in any state after a panic, so it is
+                // dropped and never polled again. A panic while dropping it
+                // is ignored, the first payload is the one that matters.
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| Self::drop_future(ptr)));
+
+                // Wakes that happened while running don't reschedule the
+                // task, so clearing SCHEDULED is enough to forget them.
+                (*(raw.header as *mut Header)).state = (state & !RUNNING & !SCHEDULED) | CLOSED;
+
+                executor::report_task_panic(&*payload);
+
+                // Keep the payload for the `JoinHandle`, if there is one.
+                if state & HANDLE != 0 {
+                    (*(raw.header as *mut Header)).panic = Some(payload);
+                }
+
+                // Notify the awaiter that the task has panicked.
+                (*(raw.header as *mut Header)).notify(None);
+            }
+            Ok(Poll::Ready(out)) => {
                 println!("poll is ready");
                 Self::drop_future(ptr);
                 raw.output.write(out);
@@ -365,7 +460,7 @@ where
 
                 drop(output);
             }
-            Poll::Pending => {
+            Ok(Poll::Pending) => {
                 println!("Task is pending");
                 // The task is still not completed.
 
@@ -408,6 +503,16 @@ where
     }
 }
 
+/// Makes the task-local values of the previous task current again when it's
+/// dropped, even if dropping a future panics.
+struct RestoreLocals(*const TaskLocals);
+
+impl Drop for RestoreLocals {
+    fn drop(&mut self) {
+        set_current_locals(self.0);
+    }
+}
+
 /// Memory layout of a task.
 ///
 /// This struct contains the following information:
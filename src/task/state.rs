@@ -33,8 +33,9 @@ pub(crate) const COMPLETED: u8 = 1 << 2;
 /// If a task is closed, that means it's either canceled or its output has been
 /// consumed by the [`JoinHandle`]. A task becomes closed when:
 ///
-/// 1. It gets canceled by [`Task::cancel()`], [`Task::drop()`], or
-/// [`JoinHandle::cancel()`]. 2. Its output gets awaited by the [`JoinHandle`].
+/// 1. It gets canceled by [`Task::drop()`], [`JoinHandle::abort()`] or
+///    [`JoinHandle::cancel()`].
+/// 2. Its output gets awaited by the [`JoinHandle`].
 /// 3. It panics while polling the future.
 /// 4. It is completed and the [`JoinHandle`] gets dropped.
 ///
@@ -1,6 +1,10 @@
-use std::{future::Future, marker::PhantomData, mem, ptr::NonNull};
+use std::{future::Future, marker::PhantomData, mem, ptr::NonNull, rc::Rc, sync::Arc};
 
-use super::{header::Header, join_handle::JoinHandle, raw::RawTask, state::SCHEDULED};
+use crate::sys::RemoteNotifier;
+
+use super::{
+    header::Header, join_handle::JoinHandle, live_tasks::LiveTasks, raw::RawTask, state::SCHEDULED,
+};
 
 #[derive(Debug)]
 pub struct Task {
@@ -56,6 +60,15 @@ impl Drop for Task {
     }
 }
 
+/// Where a new task belongs and how it's called.
+pub(crate) struct TaskMeta {
+    pub(crate) executor_id: usize,
+    pub(crate) notifier: Arc<RemoteNotifier>,
+    pub(crate) name: Option<String>,
+    pub(crate) task_queue: usize,
+    pub(crate) live_tasks: Rc<LiveTasks>,
+}
+
 /// Creates a new local task.
 ///
 /// This constructor returns a [`Task`] reference that runs the future and a
@@ -64,16 +77,12 @@ impl Drop for Task {
 /// When run, the task polls `future`. When woken up, it gets scheduled for
 /// running by the `schedule` function.
 ///
-pub(crate) fn create_task<F, R, S>(
-    executor_id: usize,
-    future: F,
-    schedule: S,
-) -> (Task, JoinHandle<R>)
+pub(crate) fn create_task<F, R, S>(meta: TaskMeta, future: F, schedule: S) -> (Task, JoinHandle<R>)
 where
     F: Future<Output = R>,
     S: Fn(Task),
 {
-    let raw_task = RawTask::<_, R, S>::allocate(future, schedule, executor_id);
+    let raw_task = RawTask::<_, R, S>::allocate(future, schedule, meta);
 
     let task = Task { raw_task };
     let handle = JoinHandle {
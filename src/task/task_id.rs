@@ -0,0 +1,45 @@
+use std::{
+    cell::Cell,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Id that will be given to the next task that is spawned.
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(0);
+
+std::thread_local! {
+    /// Id of the task being polled on this thread.
+    static CURRENT_TASK: Cell<Option<TaskId>> = const { Cell::new(None) };
+}
+
+/// Identifies a task among all the tasks of the process. Ids are given out
+/// in increasing order as tasks are spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    pub(crate) fn next() -> TaskId {
+        TaskId(NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Returns the id of the task that is running, or `None` if this isn't
+/// called from within a task.
+pub fn current_task_id() -> Option<TaskId> {
+    CURRENT_TASK.with(Cell::get)
+}
+
+/// Sets the id of the task being polled, and returns the previous one.
+pub(crate) fn set_current_task_id(id: Option<TaskId>) -> Option<TaskId> {
+    CURRENT_TASK.with(|current| current.replace(id))
+}
@@ -0,0 +1,339 @@
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    mem,
+    pin::Pin,
+    ptr,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+/// Declares task-local keys of type [`LocalKey`].
+///
+/// ```
+/// mini_async_runtime::task_local! {
+///     static REQUEST_ID: u64;
+///     pub static DEADLINE: std::time::Instant;
+/// }
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __task_local_inner {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::task_local::LocalKey<$t> =
+            $crate::task::task_local::LocalKey::__new();
+    };
+}
+
+/// A key for task-local data, declared with [`task_local!`].
+///
+/// A value is set by [`LocalKey::scope`] the first time the future it wraps
+/// is polled, and stays with the task that polls it until that future
+/// completes or is dropped. It follows the task across `.await` points and
+/// is visible whenever the executor polls the task or drops its future,
+/// without leaking into the other tasks of the executor.
+/// Tasks spawned from within a scope don't inherit its value.
+///
+/// [`LocalKey::sync_scope`] sets a value while a closure runs. Outside of a
+/// task, values are kept with the thread instead.
+pub struct LocalKey<T: 'static> {
+    // Keeps the statics of different keys at different addresses, which
+    // identify the keys
+    _unique: u8,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn __new() -> Self {
+        LocalKey {
+            _unique: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the value of the key to `value` while `future` runs.
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+        TaskLocalFuture {
+            key: self,
+            value: Rc::new(value),
+            scope: None,
+            future: Some(future),
+        }
+    }
+
+    /// Sets the value of the key to `value` while `f` runs.
+    pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Guard(u64);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                with_current(|locals| locals.leave(self.0));
+            }
+        }
+
+        let _guard = Guard(with_current(|locals| {
+            locals.enter(self.id(), Rc::new(value))
+        }));
+        f()
+    }
+
+    /// Runs `f` with a reference to the value of the key.
+    ///
+    /// Panics if the key isn't set by an enclosing scope.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.try_with(f)
+            .expect("cannot access a task-local value outside of its scope")
+    }
+
+    /// Runs `f` with a reference to the value of the key, or returns an
+    /// error if the key isn't set by an enclosing scope.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        // The value is cloned out, so that `f` may enter other scopes
+        let value =
+            with_current(|locals| locals.get(self.id())).ok_or(AccessError { _private: () })?;
+        let value = value
+            .downcast_ref::<T>()
+            .expect("task-local keys are unique to their type");
+        Ok(f(value))
+    }
+
+    /// Returns a copy of the value of the key.
+    ///
+    /// Panics if the key isn't set by an enclosing scope.
+    pub fn get(&'static self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    fn id(&'static self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+/// The values of the task-local keys set by the scopes of a task, stored in
+/// its header.
+///
+/// Scopes form a stack, the value of a key is the one of its latest scope.
+/// Entering a scope pushes its value, and polling it again moves the value
+/// back to the top, so that scopes of the same key that are polled in turn,
+/// like joined futures, each see their own value.
+#[derive(Debug, Default)]
+pub(crate) struct TaskLocals {
+    scopes: RefCell<Vec<Scope>>,
+}
+
+struct Scope {
+    id: u64,
+    key: usize,
+    value: Rc<dyn Any>,
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope").field("id", &self.id).finish()
+    }
+}
+
+impl TaskLocals {
+    /// Sets `value` for `key` until the scope whose id is returned is left.
+    fn enter(&self, key: usize, value: Rc<dyn Any>) -> u64 {
+        let id = NEXT_SCOPE.with(|next| next.replace(next.get() + 1));
+        self.scopes.borrow_mut().push(Scope { id, key, value });
+        id
+    }
+
+    /// Moves the value of scope `id` back to the top of the stack, or sets it
+    /// again if the scope was left.
+    fn reenter(&self, id: u64, key: usize, value: &Rc<dyn Any>) {
+        let mut scopes = self.scopes.borrow_mut();
+        let scope = match scopes.iter().position(|scope| scope.id == id) {
+            Some(index) => scopes.remove(index),
+            None => Scope {
+                id,
+                key,
+                value: value.clone(),
+            },
+        };
+        scopes.push(scope);
+    }
+
+    fn leave(&self, id: u64) {
+        self.scopes.borrow_mut().retain(|scope| scope.id != id);
+    }
+
+    fn get(&self, key: usize) -> Option<Rc<dyn Any>> {
+        self.scopes
+            .borrow()
+            .iter()
+            .rev()
+            .find(|scope| scope.key == key)
+            .map(|scope| scope.value.clone())
+    }
+}
+
+thread_local! {
+    // The locals of the task being run, or null outside of tasks
+    static CURRENT: Cell<*const TaskLocals> = const { Cell::new(ptr::null()) };
+    static THREAD_LOCALS: TaskLocals = TaskLocals::default();
+    static NEXT_SCOPE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Makes `locals` the ones of the task being run, and returns the previous
+/// ones.
+///
+/// `locals` must stay alive until they are replaced again.
+pub(crate) fn set_current_locals(locals: *const TaskLocals) -> *const TaskLocals {
+    CURRENT.with(|current| current.replace(locals))
+}
+
+fn with_current<R>(f: impl FnOnce(&TaskLocals) -> R) -> R {
+    let current = CURRENT.with(Cell::get);
+    if current.is_null() {
+        THREAD_LOCALS.with(f)
+    } else {
+        // Set by the task being run, which outlives this call
+        f(unsafe { &*current })
+    }
+}
+
+/// A future that sets the value of a [`LocalKey`] for the task that polls the
+/// future it wraps. Returned by [`LocalKey::scope`].
+pub struct TaskLocalFuture<T: 'static, F> {
+    key: &'static LocalKey<T>,
+    value: Rc<dyn Any>,
+    // Set once the future was first polled
+    scope: Option<u64>,
+    // `None` once it completed, or while it's dropped within the scope
+    future: Option<F>,
+}
+
+impl<T: 'static, F> TaskLocalFuture<T, F> {
+    /// Puts the value of the scope on top of the locals of the task, and
+    /// returns a guard that leaves the scope when it's dropped.
+    fn enter(&mut self) -> ScopeGuard {
+        let key = self.key.id();
+        let id = match self.scope {
+            Some(id) => {
+                with_current(|locals| locals.reenter(id, key, &self.value));
+                id
+            }
+            None => *self
+                .scope
+                .insert(with_current(|locals| locals.enter(key, self.value.clone()))),
+        };
+        ScopeGuard(id)
+    }
+}
+
+struct ScopeGuard(u64);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        with_current(|locals| locals.leave(self.0));
+    }
+}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The future is never moved out of `self`, it's only dropped in place
+        let this = unsafe { self.get_unchecked_mut() };
+        let guard = this.enter();
+        let mut future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let poll = future
+            .as_mut()
+            .as_pin_mut()
+            .expect("`TaskLocalFuture` polled after it completed")
+            .poll(cx);
+        match poll {
+            Poll::Ready(output) => {
+                // Dropped within the scope, so that its destructors can see
+                // the value
+                future.set(None);
+                Poll::Ready(output)
+            }
+            Poll::Pending => {
+                // The value stays with the task until the next poll
+                mem::forget(guard);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T: 'static, F> Drop for TaskLocalFuture<T, F> {
+    fn drop(&mut self) {
+        // The thread-locals are gone if the thread is exiting
+        if THREAD_LOCALS.try_with(|_| ()).is_err() {
+            return;
+        }
+        if self.future.is_some() {
+            // Drop the future within the scope, so that its destructors can
+            // see the value.
+            let _guard = self.enter();
+            let mut future = unsafe { Pin::new_unchecked(&mut self.future) };
+            future.set(None);
+        } else if let Some(id) = self.scope {
+            drop(ScopeGuard(id));
+        }
+    }
+}
+
+impl<T: 'static, F> fmt::Debug for TaskLocalFuture<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskLocalFuture")
+            .field("key", self.key)
+            .finish()
+    }
+}
+
+/// Error returned by [`LocalKey::try_with`] when the key isn't set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessError {
+    _private: (),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("task-local value not set")
+    }
+}
+
+impl std::error::Error for AccessError {}
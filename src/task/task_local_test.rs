@@ -0,0 +1,137 @@
+use std::{cell::Cell, rc::Rc};
+
+use futures_lite::future;
+
+use crate::executor::{local_executor::LocalExecutor, spawn_local, yield_now};
+
+crate::task_local! {
+    static REQUEST_ID: u64;
+    static TRACE: Cell<usize>;
+}
+
+#[test]
+fn task_local_follows_the_task_across_awaits() {
+    let local_ex = LocalExecutor::default();
+    let ids = local_ex.run(async {
+        let handles: Vec<_> = (0..3)
+            .map(|id| {
+                spawn_local(REQUEST_ID.scope(id, async move {
+                    let mut seen = Vec::new();
+                    for _ in 0..3 {
+                        seen.push(REQUEST_ID.get());
+                        yield_now().await;
+                    }
+                    assert!(seen.iter().all(|seen| *seen == id));
+                    id
+                }))
+            })
+            .collect();
+        // The values don't leak out of their tasks
+        assert!(REQUEST_ID.try_with(|_| ()).is_err());
+        let mut ids = Vec::new();
+        for handle in handles {
+            ids.push(handle.await.unwrap());
+        }
+        ids
+    });
+    assert_eq!(ids, vec![0, 1, 2]);
+}
+
+#[test]
+fn task_local_scopes_nest() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(REQUEST_ID.scope(1, async {
+        assert_eq!(REQUEST_ID.get(), 1);
+        REQUEST_ID
+            .scope(2, async {
+                yield_now().await;
+                assert_eq!(REQUEST_ID.get(), 2);
+            })
+            .await;
+        assert_eq!(REQUEST_ID.get(), 1);
+        REQUEST_ID.sync_scope(3, || assert_eq!(REQUEST_ID.get(), 3));
+        assert_eq!(REQUEST_ID.get(), 1);
+    }));
+}
+
+#[test]
+fn task_local_is_set_while_the_future_is_dropped() {
+    struct Probe;
+
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            TRACE.with(|trace| trace.set(trace.get() + 1));
+        }
+    }
+
+    let trace = TRACE.sync_scope(Cell::new(0), || {
+        let probe = Probe;
+        let future = TRACE.scope(Cell::new(10), async move {
+            let _probe = probe;
+        });
+        drop(future);
+        TRACE.get().get()
+    });
+    // The probe was dropped within the inner scope
+    assert_eq!(trace, 0);
+}
+
+mod shadowed {
+    // The macro must not pick this up instead of the standard library
+    mod std {}
+
+    crate::task_local! {
+        pub(super) static SHADOWED: u32;
+    }
+}
+
+#[test]
+fn task_local_ignores_shadowed_std() {
+    assert_eq!(
+        shadowed::SHADOWED.sync_scope(7, || shadowed::SHADOWED.get()),
+        7
+    );
+}
+
+#[test]
+fn joined_scopes_see_their_own_value() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let scoped = |id: u64| {
+            REQUEST_ID.scope(id, async move {
+                for _ in 0..3 {
+                    assert_eq!(REQUEST_ID.get(), id);
+                    yield_now().await;
+                }
+            })
+        };
+        future::zip(scoped(1), scoped(2)).await;
+        assert!(REQUEST_ID.try_with(|_| ()).is_err());
+    });
+}
+
+#[test]
+fn task_local_is_set_while_a_canceled_task_is_dropped() {
+    struct Probe(Rc<Cell<Option<u64>>>);
+
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            self.0.set(REQUEST_ID.try_with(|id| *id).ok());
+        }
+    }
+
+    let local_ex = LocalExecutor::default();
+    let seen = local_ex.run(async {
+        let seen = Rc::new(Cell::new(None));
+        let probe = Probe(seen.clone());
+        let handle = spawn_local(REQUEST_ID.scope(4, async move {
+            let _probe = probe;
+            future::pending::<()>().await
+        }));
+        yield_now().await;
+        assert!(handle.cancel().await.is_err());
+        assert!(REQUEST_ID.try_with(|_| ()).is_err());
+        seen.get()
+    });
+    assert_eq!(seen, Some(4));
+}
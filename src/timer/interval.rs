@@ -0,0 +1,119 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, Stream};
+
+use super::sleep::Timer;
+
+/// What an [`Interval`] does when ticks were missed because it wasn't polled
+/// in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTickBehavior {
+    /// Yields the missed ticks right away, one after the other, so that the
+    /// interval catches up with its schedule.
+    #[default]
+    Burst,
+    /// Yields one late tick, and schedules the following ones a full period
+    /// after it was yielded.
+    Delay,
+    /// Yields one late tick, and skips the missed ones so that the next
+    /// tick is back on the original schedule.
+    Skip,
+}
+
+/// Creates an interval whose first tick completes immediately, and the
+/// following ones every `period`.
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now(), period)
+}
+
+/// Creates an interval whose first tick completes at `start`, and the
+/// following ones every `period`.
+///
+/// Panics if `period` is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "An Interval needs a non-zero period");
+    Interval {
+        period,
+        timer: Timer::at(start),
+        missed_tick_behavior: MissedTickBehavior::default(),
+    }
+}
+
+/// Ticks at a fixed period. Each tick yields the instant it was scheduled
+/// for.
+///
+/// Ticks are driven by the executor's timers, see [`Timer`].
+#[derive(Debug)]
+pub struct Interval {
+    period: Duration,
+    // Fires at the next scheduled tick
+    timer: Timer,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+impl Interval {
+    /// Waits for the next tick and returns the instant it was scheduled for.
+    pub async fn tick(&mut self) -> Instant {
+        future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.timer).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let tick = self.timer.deadline();
+        let next = self.next_tick(tick, Instant::now());
+        self.timer.reset(next);
+        Poll::Ready(tick)
+    }
+
+    /// Returns when the tick after the one scheduled at `tick` is due, if
+    /// it's yielded at `now`.
+    fn next_tick(&self, tick: Instant, now: Instant) -> Instant {
+        let next = tick + self.period;
+        if now < next {
+            return next;
+        }
+        match self.missed_tick_behavior {
+            MissedTickBehavior::Burst => next,
+            MissedTickBehavior::Delay => now + self.period,
+            MissedTickBehavior::Skip => {
+                let missed = (now - next).as_nanos() / self.period.as_nanos() + 1;
+                next + Duration::from_nanos((self.period.as_nanos() * missed) as u64)
+            }
+        }
+    }
+
+    /// Restarts the schedule so that the next tick is a full period from
+    /// now.
+    pub fn reset(&mut self) {
+        self.timer.reset(Instant::now() + self.period);
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        self.get_mut().poll_tick(cx).map(Some)
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::{Duration, Instant};
+
+use self::sleep::Timer;
+
+pub mod interval;
+pub mod sleep;
+pub mod timeout;
+mod timer_test;
+pub mod uring_timer;
+#[cfg(feature = "bench-internals")]
+#[doc(hidden)]
+pub mod wheel;
+#[cfg(not(feature = "bench-internals"))]
+pub(crate) mod wheel;
+mod wheel_test;
+
+/// Waits until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Timer {
+    Timer::new(duration)
+}
+
+/// Waits until `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Timer {
+    Timer::at(deadline)
+}
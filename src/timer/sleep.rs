@@ -0,0 +1,103 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use crate::{executor::get_reactor, reactor::Reactor};
+
+/// A future that completes once its deadline passes.
+///
+/// The timer is registered with the executor the first time it's polled, so
+/// that the executor doesn't sleep past the deadline and wakes the task when
+/// it passes. Dropping the timer cancels it.
+pub struct Timer {
+    deadline: Instant,
+    // Set while the timer is registered with the reactor
+    registration: Option<(u64, Weak<Reactor>)>,
+}
+
+impl Timer {
+    /// Creates a timer that fires after `duration`.
+    pub fn new(duration: Duration) -> Timer {
+        Timer::at(deadline_after(duration))
+    }
+
+    /// Creates a timer that fires at `deadline`.
+    pub fn at(deadline: Instant) -> Timer {
+        Timer {
+            deadline,
+            registration: None,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Moves the deadline of the timer to `deadline`, whether it already
+    /// fired or not.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.cancel();
+        self.deadline = deadline;
+    }
+
+    fn cancel(&mut self) {
+        if let Some((id, reactor)) = self.registration.take() {
+            if let Some(reactor) = reactor.upgrade() {
+                reactor.remove_timer(id);
+            }
+        }
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if Instant::now() >= this.deadline {
+            this.cancel();
+            return Poll::Ready(());
+        }
+        match &this.registration {
+            Some((id, reactor)) => {
+                if let Some(reactor) = reactor.upgrade() {
+                    reactor.set_timer_waker(*id, cx.waker());
+                }
+            }
+            None => {
+                let reactor = get_reactor();
+                let id = reactor.insert_timer(this.deadline, cx.waker().clone());
+                this.registration = Some((id, Rc::downgrade(&reactor)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("deadline", &self.deadline)
+            .field("registered", &self.registration.is_some())
+            .finish()
+    }
+}
+
+/// Returns the instant `duration` from now, or one far in the future if that
+/// can't be represented.
+pub(crate) fn deadline_after(duration: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(duration)
+        .unwrap_or_else(|| now + Duration::from_secs(86400 * 365 * 30))
+}
@@ -0,0 +1,112 @@
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use super::sleep::Timer;
+
+/// Runs `future` for at most `duration`.
+///
+/// Resolves to the output of `future` if it completes in time, or to
+/// `Err(Elapsed)` otherwise, in which case `future` is dropped.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout::new(future, Timer::new(duration))
+}
+
+/// Runs `future` until `deadline` at most. See [`timeout`].
+pub fn timeout_at<F: Future>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout::new(future, Timer::at(deadline))
+}
+
+/// Bounds the time a future may take, see [`timeout`].
+pub trait Deadline: Future + Sized {
+    /// Runs the future for at most `duration`.
+    fn timeout(self, duration: Duration) -> Timeout<Self> {
+        timeout(duration, self)
+    }
+
+    /// Runs the future until `deadline` at most.
+    fn timeout_at(self, deadline: Instant) -> Timeout<Self> {
+        timeout_at(deadline, self)
+    }
+}
+
+impl<F: Future> Deadline for F {}
+
+/// Future returned by [`timeout`] and [`timeout_at`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    // Dropped in place once the deadline passes
+    future: Option<F>,
+    timer: Timer,
+}
+
+impl<F> Timeout<F> {
+    fn new(future: F, timer: Timer) -> Self {
+        Timeout {
+            future: Some(future),
+            timer,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.timer.deadline()
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The future is never moved out of `self`, it's only dropped in place
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let Some(inner) = future.as_mut().as_pin_mut() else {
+            return Poll::Ready(Err(Elapsed { _private: () }));
+        };
+        if let Poll::Ready(output) = inner.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.timer).poll(cx) {
+            Poll::Ready(()) => {
+                future.set(None);
+                Poll::Ready(Err(Elapsed { _private: () }))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F> fmt::Debug for Timeout<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("timer", &self.timer)
+            .field("elapsed", &self.future.is_none())
+            .finish()
+    }
+}
+
+/// Error returned by [`timeout`] when the deadline passes before the future
+/// completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed {
+    _private: (),
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+impl From<Elapsed> for io::Error {
+    fn from(elapsed: Elapsed) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, elapsed)
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    cell::{Cell, RefCell},
+    io,
+    net::TcpListener,
+    rc::Rc,
+    task::Poll,
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, pin, StreamExt};
+
+use crate::{
+    executor::{get_reactor, local_executor::LocalExecutor, spawn_local},
+    pollable::Async,
+};
+
+use super::{
+    interval::{interval, interval_at, MissedTickBehavior},
+    sleep,
+    sleep::Timer,
+    sleep_until,
+    timeout::{timeout, Deadline},
+    uring_timer::UringTimer,
+};
+
+#[test]
+fn sleep_waits_for_the_duration() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let start = Instant::now();
+        sleep(Duration::from_millis(20)).await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(get_reactor().timers.borrow().len(), 0);
+    });
+}
+
+#[test]
+fn timers_fire_in_deadline_order() {
+    let local_ex = LocalExecutor::default();
+    let order = Rc::new(RefCell::new(Vec::new()));
+    local_ex.run(async {
+        let start = Instant::now();
+        let handles: Vec<_> = [30, 10, 20]
+            .into_iter()
+            .map(|millis| {
+                let order = order.clone();
+                spawn_local(async move {
+                    sleep_until(start + Duration::from_millis(millis)).await;
+                    order.borrow_mut().push(millis);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    assert_eq!(*order.borrow(), vec![10, 20, 30]);
+}
+
+#[test]
+fn dropped_timer_is_canceled() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let mut timer = sleep(Duration::from_secs(60));
+        assert!(future::poll_once(&mut timer).await.is_none());
+        assert_eq!(get_reactor().timers.borrow().len(), 1);
+        drop(timer);
+        assert_eq!(get_reactor().timers.borrow().len(), 0);
+    });
+}
+
+#[test]
+fn reset_timer_moves_the_deadline() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let mut timer = Timer::new(Duration::from_secs(60));
+        assert!(future::poll_once(&mut timer).await.is_none());
+        let start = Instant::now();
+        timer.reset(start + Duration::from_millis(10));
+        assert_eq!(timer.deadline(), start + Duration::from_millis(10));
+        timer.await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(get_reactor().timers.borrow().len(), 0);
+    });
+}
+
+#[test]
+fn uring_timer_fires_in_the_ring() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let start = Instant::now();
+        UringTimer::new(Duration::from_millis(20)).await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        // Never registered with the executor's timers
+        assert_eq!(get_reactor().timers.borrow().len(), 0);
+    });
+}
+
+#[test]
+fn dropped_uring_timer_is_canceled() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let start = Instant::now();
+        let long = UringTimer::new(Duration::from_secs(60));
+        let short = UringTimer::new(Duration::from_millis(10));
+        future::or(long, short).await;
+        assert!(start.elapsed() < Duration::from_secs(60));
+        // Lets the removal and the canceled timeout complete
+        sleep(Duration::from_millis(10)).await;
+    });
+}
+
+#[test]
+fn timeout_returns_the_output_in_time() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let res = timeout(Duration::from_secs(60), async {
+            sleep(Duration::from_millis(1)).await;
+            5
+        })
+        .await;
+        assert_eq!(res, Ok(5));
+        // The timer of the timeout was canceled along with it
+        assert_eq!(get_reactor().timers.borrow().len(), 0);
+    });
+}
+
+#[test]
+fn timeout_drops_the_future_once_elapsed() {
+    struct SetOnDrop(Rc<Cell<bool>>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let dropped = Rc::new(Cell::new(false));
+        let guard = SetOnDrop(dropped.clone());
+        let fut = timeout(Duration::from_millis(10), async move {
+            let _guard = guard;
+            future::pending::<()>().await
+        });
+        pin!(fut);
+        assert!(fut.as_mut().await.is_err());
+        assert!(dropped.get());
+        // Polling again doesn't poll the dropped future
+        assert!(fut.await.is_err());
+    });
+}
+
+#[test]
+fn timeout_bounds_accept() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+        let start = Instant::now();
+        let err = listener
+            .accept()
+            .timeout(Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+
+        let deadline = Instant::now() + Duration::from_millis(5);
+        let res = async { 1 }.timeout_at(deadline).await;
+        assert_eq!(res, Ok(1));
+    });
+}
+
+#[test]
+fn io_wake_leaves_nothing_in_the_kernel() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let in_kernel = || get_reactor().sys.main_ring.borrow().in_kernel;
+        // Park once so that the notifier's poll is in the ring
+        sleep(Duration::from_millis(1)).await;
+        let before = in_kernel();
+
+        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+        let addr = listener.get_ref().local_addr().unwrap();
+        let connector = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            std::net::TcpStream::connect(addr).unwrap()
+        });
+        let start = Instant::now();
+        listener
+            .accept()
+            .timeout(Duration::from_secs(60))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(60));
+        connector.join().unwrap();
+        assert_eq!(in_kernel(), before);
+    });
+}
+
+#[test]
+fn sleeps_are_bounded_without_enter_timeouts() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        // As on kernels older than 5.11
+        get_reactor().sys.main_ring.borrow_mut().enter_timeout = false;
+        let start = Instant::now();
+        sleep(Duration::from_millis(20)).await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // Woken up by I/O before the timeout sqe fires, which is then skipped
+        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+        let addr = listener.get_ref().local_addr().unwrap();
+        let connector = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            std::net::TcpStream::connect(addr).unwrap()
+        });
+        listener
+            .accept()
+            .timeout(Duration::from_millis(30))
+            .await
+            .unwrap()
+            .unwrap();
+        connector.join().unwrap();
+        sleep(Duration::from_millis(40)).await;
+        assert_eq!(get_reactor().timers.borrow().len(), 0);
+    });
+}
+
+#[test]
+fn remote_wake_while_parked_on_a_timer_leaves_nothing_in_the_kernel() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let in_kernel = || get_reactor().sys.main_ring.borrow().in_kernel;
+        sleep(Duration::from_millis(1)).await;
+        let before = in_kernel();
+
+        // The executor parks until the timer is due, and is woken up early by
+        // another thread through the notifier
+        let timer = spawn_local(sleep(Duration::from_secs(60)));
+        let woken = Rc::new(Cell::new(false));
+        future::poll_fn(|cx| {
+            if woken.replace(true) {
+                return Poll::Ready(());
+            }
+            let waker = cx.waker().clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                waker.wake();
+            });
+            Poll::Pending
+        })
+        .await;
+        // Parking again puts the notifier's poll back in the ring
+        sleep(Duration::from_millis(1)).await;
+        assert_eq!(in_kernel(), before);
+        drop(timer);
+    });
+}
+
+#[test]
+fn interval_ticks_every_period() {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let start = Instant::now();
+        let ticks: Vec<_> = interval(Duration::from_millis(10)).take(3).collect().await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(ticks[1] - ticks[0], Duration::from_millis(10));
+        assert_eq!(ticks[2] - ticks[1], Duration::from_millis(10));
+    });
+}
+
+fn ticks_after_missing(behavior: MissedTickBehavior) -> (Instant, Vec<Instant>) {
+    let local_ex = LocalExecutor::default();
+    local_ex.run(async {
+        let start = Instant::now();
+        let mut interval = interval_at(start, Duration::from_millis(20));
+        interval.set_missed_tick_behavior(behavior);
+        let mut ticks = vec![interval.tick().await];
+        // Blocks the executor past two ticks
+        thread::sleep(Duration::from_millis(50));
+        for _ in 0..3 {
+            ticks.push(interval.tick().await);
+        }
+        (start, ticks)
+    })
+}
+
+#[test]
+fn interval_bursts_missed_ticks() {
+    let (start, ticks) = ticks_after_missing(MissedTickBehavior::Burst);
+    let expected: Vec<_> = (0..4)
+        .map(|i| start + Duration::from_millis(20) * i)
+        .collect();
+    assert_eq!(ticks, expected);
+}
+
+#[test]
+fn interval_skips_missed_ticks() {
+    let (start, ticks) = ticks_after_missing(MissedTickBehavior::Skip);
+    let expected: Vec<_> = [0, 20, 60, 80]
+        .into_iter()
+        .map(|millis| start + Duration::from_millis(millis))
+        .collect();
+    assert_eq!(ticks, expected);
+}
+
+#[test]
+fn interval_delays_after_missed_ticks() {
+    let (start, ticks) = ticks_after_missing(MissedTickBehavior::Delay);
+    assert_eq!(ticks[1], start + Duration::from_millis(20));
+    // The schedule restarts from when the late tick was yielded
+    assert!(ticks[2] >= start + Duration::from_millis(70));
+    assert_eq!(ticks[3] - ticks[2], Duration::from_millis(20));
+}
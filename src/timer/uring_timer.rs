@@ -0,0 +1,103 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    executor::get_reactor,
+    reactor::Reactor,
+    sys::{Source, SourceType},
+};
+
+use super::sleep::deadline_after;
+
+/// A future that completes once its deadline passes, submitted to the ring
+/// as a timeout operation of its own.
+///
+/// Unlike [`Timer`](super::sleep::Timer), which the executor tracks and fires
+/// itself, the kernel completes this timer, so it also wakes an executor that
+/// is sleeping in the ring for other reasons. Dropping the timer cancels the
+/// timeout in the ring.
+pub struct UringTimer {
+    deadline: Instant,
+    // The timeout in the ring and its id, once the timer was polled
+    submitted: Option<(Source, u64, Weak<Reactor>)>,
+}
+
+impl UringTimer {
+    /// Creates a timer that fires after `duration`.
+    pub fn new(duration: Duration) -> UringTimer {
+        UringTimer::at(deadline_after(duration))
+    }
+
+    /// Creates a timer that fires at `deadline`.
+    pub fn at(deadline: Instant) -> UringTimer {
+        UringTimer {
+            deadline,
+            submitted: None,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl Future for UringTimer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        match &this.submitted {
+            // The timeout is relative, so it's only submitted once the
+            // remaining time is known
+            None => {
+                let remaining = this.deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Poll::Ready(());
+                }
+                let reactor = get_reactor();
+                let source = Source::new(-1, SourceType::Timeout(remaining.into()), None);
+                source.add_waiter(cx.waker().clone());
+                let id = reactor.sys.timeout(&source);
+                this.submitted = Some((source, id, Rc::downgrade(&reactor)));
+                Poll::Pending
+            }
+            Some((source, _, _)) => {
+                // Only `ETIME` is expected, the timeout is only canceled on
+                // drop
+                if source.take_result().is_some() {
+                    this.submitted = None;
+                    return Poll::Ready(());
+                }
+                source.add_waiter(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for UringTimer {
+    fn drop(&mut self) {
+        if let Some((source, id, reactor)) = self.submitted.take() {
+            if source.take_result().is_none() {
+                if let Some(reactor) = reactor.upgrade() {
+                    reactor.sys.cancel_timeout(id);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for UringTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UringTimer")
+            .field("deadline", &self.deadline)
+            .field("submitted", &self.submitted.is_some())
+            .finish()
+    }
+}
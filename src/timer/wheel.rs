@@ -0,0 +1,299 @@
+use std::{
+    mem,
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+/// Number of slots of every level, and the factor between the resolution of
+/// consecutive levels.
+const SLOTS: usize = 64;
+const SLOT_BITS: u32 = SLOTS.trailing_zeros();
+
+/// Level `i` spans `64^(i + 1)` ticks of a millisecond, so 7 levels cover
+/// more than a century.
+const LEVELS: usize = 7;
+
+/// Deadlines further away than this are clamped to it.
+const MAX_TICK: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// Level of the entries that are in `TimerWheel::expired` rather than in a
+/// slot.
+const EXPIRED: u8 = u8::MAX;
+
+/// The pending timers of an executor, stored in a hashed hierarchical timing
+/// wheel with a resolution of a millisecond.
+///
+/// Slot `s` of level `l` holds the timers due within the `s`-th span of
+/// `64^l` ticks of the current span of `64^(l + 1)` ticks. Timers are placed
+/// on the lowest level whose span contains both the current tick and their
+/// deadline. Once the wheel reaches the slot of a timer of a higher level,
+/// the timer cascades down to a lower one, until it reaches level 0 and
+/// fires. Inserting and canceling a timer are O(1).
+///
+/// Canceling a timer only frees its entry. Its key stays in its slot and is
+/// skipped once the slot is processed, unless the slot becomes empty or is
+/// mostly made of canceled keys, in which case it's cleaned up right away.
+#[derive(Debug)]
+pub struct TimerWheel {
+    // Tick 0 of the wheel
+    start: Instant,
+    // Every timer due at or before this tick was fired
+    elapsed: u64,
+    levels: Vec<Level>,
+    entries: Vec<Entry>,
+    free: Vec<u32>,
+    // Timers whose deadline had already passed when they were inserted
+    expired: Vec<Key>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Level {
+    // Bit `s` is set if slot `s` has live timers
+    occupied: u64,
+    slots: Vec<Vec<Key>>,
+    live: [u32; SLOTS],
+}
+
+#[derive(Debug)]
+struct Entry {
+    tick: u64,
+    generation: u32,
+    // `None` once the timer fired or was canceled
+    waker: Option<Waker>,
+    level: u8,
+    slot: u8,
+}
+
+/// An index into `entries`, along with the generation of the entry when the
+/// key was handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Key {
+    index: u32,
+    generation: u32,
+}
+
+impl Key {
+    fn from_id(id: u64) -> Key {
+        Key {
+            index: id as u32,
+            generation: (id >> 32) as u32,
+        }
+    }
+
+    fn id(self) -> u64 {
+        (self.generation as u64) << 32 | self.index as u64
+    }
+}
+
+impl TimerWheel {
+    pub fn new(start: Instant) -> Self {
+        TimerWheel {
+            start,
+            elapsed: 0,
+            levels: (0..LEVELS)
+                .map(|_| Level {
+                    occupied: 0,
+                    slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+                    live: [0; SLOTS],
+                })
+                .collect(),
+            entries: Vec::new(),
+            free: Vec::new(),
+            expired: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Registers a timer that wakes `waker` once `deadline` passes, and
+    /// returns its id.
+    pub fn insert(&mut self, deadline: Instant, waker: Waker) -> u64 {
+        // Rounded up, so that timers never fire early
+        let nanos = deadline.saturating_duration_since(self.start).as_nanos();
+        let tick = nanos.div_ceil(1_000_000).min(MAX_TICK as u128) as u64;
+        let entry = Entry {
+            tick,
+            generation: 0,
+            waker: Some(waker),
+            level: 0,
+            slot: 0,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                let generation = self.entries[index as usize].generation;
+                self.entries[index as usize] = Entry {
+                    generation,
+                    ..entry
+                };
+                index
+            }
+            None => {
+                self.entries.push(entry);
+                (self.entries.len() - 1) as u32
+            }
+        };
+        let key = Key {
+            index,
+            generation: self.entries[index as usize].generation,
+        };
+        self.len += 1;
+        self.place(key);
+        key.id()
+    }
+
+    /// Replaces the waker of the timer, unless it already fired.
+    pub(crate) fn set_waker(&mut self, id: u64, waker: &Waker) {
+        if let Some(entry) = self.entry_mut(Key::from_id(id)) {
+            if let Some(current) = &mut entry.waker {
+                if !current.will_wake(waker) {
+                    *current = waker.clone();
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let key = Key::from_id(id);
+        let Some(entry) = self.entry_mut(key) else {
+            return;
+        };
+        if entry.waker.take().is_none() {
+            return;
+        }
+        let (level, slot) = (entry.level as usize, entry.slot as usize);
+        self.release(key.index);
+        self.len -= 1;
+
+        if level == EXPIRED as usize {
+            return;
+        }
+        let level = &mut self.levels[level];
+        level.live[slot] -= 1;
+        if level.live[slot] == 0 {
+            level.slots[slot].clear();
+            level.occupied &= !(1 << slot);
+        } else if level.slots[slot].len() > 2 * level.live[slot] as usize + 16 {
+            let entries = &self.entries;
+            level.slots[slot].retain(|key| is_live(entries, *key));
+        }
+    }
+
+    /// Number of timers that didn't fire and weren't canceled.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Removes the timers whose deadline is at or before `now` and returns
+    /// their wakers, in the order of their slots.
+    pub fn expire(&mut self, now: Instant) -> Vec<Waker> {
+        let mut fired = Vec::new();
+        for key in mem::take(&mut self.expired) {
+            self.fire(key, &mut fired);
+        }
+
+        let now_tick = now.saturating_duration_since(self.start).as_millis() as u64;
+        while let Some((level, slot, tick)) = self.next_slot() {
+            if tick > now_tick {
+                break;
+            }
+            self.elapsed = tick;
+            let lvl = &mut self.levels[level];
+            lvl.occupied &= !(1 << slot);
+            lvl.live[slot] = 0;
+            for key in mem::take(&mut lvl.slots[slot]) {
+                if !is_live(&self.entries, key) {
+                    continue;
+                }
+                if self.entries[key.index as usize].tick <= self.elapsed {
+                    self.fire(key, &mut fired);
+                } else {
+                    self.place(key);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now_tick);
+        fired
+    }
+
+    /// Returns how long until the wheel has to be processed again, or `None`
+    /// if there is no timer. This may be before the next timer fires, when
+    /// timers have to cascade down to a lower level.
+    pub(crate) fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        if self.len() == 0 {
+            return None;
+        }
+        if !self.expired.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        let (_, _, tick) = self.next_slot()?;
+        let deadline = self.start + Duration::from_millis(tick);
+        Some(deadline.saturating_duration_since(now))
+    }
+
+    /// Returns the level and index of the next slot to process, along with
+    /// the tick it starts at.
+    ///
+    /// Timers of a level are due after the ones of the levels below it, so
+    /// the next slot is the first occupied slot of the lowest level that has
+    /// one. Slots of a level before the current one are all empty.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        self.levels.iter().enumerate().find_map(|(level, lvl)| {
+            if lvl.occupied == 0 {
+                return None;
+            }
+            let slot_bits = SLOT_BITS * level as u32;
+            let current = (self.elapsed >> slot_bits) as usize % SLOTS;
+            let slot = (lvl.occupied >> current).trailing_zeros() as usize + current;
+            let level_start = self.elapsed & !((1u64 << (slot_bits + SLOT_BITS)) - 1);
+            Some((level, slot, level_start + ((slot as u64) << slot_bits)))
+        })
+    }
+
+    /// Puts the timer of `key` into the slot its deadline falls in.
+    fn place(&mut self, key: Key) {
+        let entry = &mut self.entries[key.index as usize];
+        if entry.tick <= self.elapsed {
+            entry.level = EXPIRED;
+            self.expired.push(key);
+            return;
+        }
+        // The highest bit that differs from the current tick determines the
+        // level
+        let significant = u64::BITS - 1 - (self.elapsed ^ entry.tick).leading_zeros();
+        let level = (significant / SLOT_BITS) as usize;
+        let slot = (entry.tick >> (SLOT_BITS * level as u32)) as usize % SLOTS;
+        entry.level = level as u8;
+        entry.slot = slot as u8;
+        let lvl = &mut self.levels[level];
+        lvl.slots[slot].push(key);
+        lvl.live[slot] += 1;
+        lvl.occupied |= 1 << slot;
+    }
+
+    fn fire(&mut self, key: Key, fired: &mut Vec<Waker>) {
+        if let Some(waker) = self.entry_mut(key).and_then(|entry| entry.waker.take()) {
+            self.release(key.index);
+            self.len -= 1;
+            fired.push(waker);
+        }
+    }
+
+    fn entry_mut(&mut self, key: Key) -> Option<&mut Entry> {
+        self.entries
+            .get_mut(key.index as usize)
+            .filter(|entry| entry.generation == key.generation)
+    }
+
+    /// Makes the entry available to new timers, and invalidates the keys
+    /// that point to it.
+    fn release(&mut self, index: u32) {
+        let entry = &mut self.entries[index as usize];
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(index);
+    }
+}
+
+fn is_live(entries: &[Entry], key: Key) -> bool {
+    let entry = &entries[key.index as usize];
+    entry.generation == key.generation && entry.waker.is_some()
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    task::{Wake, Waker},
+    time::{Duration, Instant},
+};
+
+use super::wheel::TimerWheel;
+
+/// Records its id when it's woken.
+struct IdWaker {
+    id: u64,
+    woken: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Wake for IdWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.lock().unwrap().push(self.id);
+    }
+}
+
+fn id_waker(id: u64, woken: &Arc<Mutex<Vec<u64>>>) -> Waker {
+    Waker::from(Arc::new(IdWaker {
+        id,
+        woken: woken.clone(),
+    }))
+}
+
+fn fire(wakers: Vec<Waker>) {
+    wakers.into_iter().for_each(Waker::wake);
+}
+
+#[test]
+fn canceled_timers_are_skipped() {
+    let woken = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+    let mut wheel = TimerWheel::new(start);
+    let first = wheel.insert(start + Duration::from_millis(1), id_waker(1, &woken));
+    wheel.insert(start + Duration::from_millis(2), id_waker(2, &woken));
+    wheel.remove(first);
+    assert_eq!(wheel.len(), 1);
+    assert_eq!(wheel.next_timeout(start), Some(Duration::from_millis(2)));
+    assert!(wheel.expire(start + Duration::from_millis(1)).is_empty());
+    fire(wheel.expire(start + Duration::from_millis(2)));
+    assert_eq!(*woken.lock().unwrap(), vec![2]);
+    assert_eq!(wheel.next_timeout(start), None);
+    assert_eq!(wheel.len(), 0);
+}
+
+#[test]
+fn stale_ids_dont_cancel_reused_entries() {
+    let woken = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+    let mut wheel = TimerWheel::new(start);
+    let first = wheel.insert(start + Duration::from_millis(5), id_waker(1, &woken));
+    wheel.remove(first);
+    let second = wheel.insert(start + Duration::from_millis(5), id_waker(2, &woken));
+    assert_ne!(first, second);
+    wheel.remove(first);
+    wheel.set_waker(first, &id_waker(3, &woken));
+    assert_eq!(wheel.len(), 1);
+    fire(wheel.expire(start + Duration::from_millis(5)));
+    assert_eq!(*woken.lock().unwrap(), vec![2]);
+}
+
+#[test]
+fn timers_cascade_and_fire_on_their_tick() {
+    let woken = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+    let mut wheel = TimerWheel::new(start);
+    let mut deadlines = vec![
+        1,
+        63,
+        64,
+        65,
+        4095,
+        4096,
+        4097,
+        262_143,
+        262_144,
+        300_000,
+        86_400_000,
+        864_000_000,
+    ];
+    for &millis in &deadlines {
+        wheel.insert(
+            start + Duration::from_millis(millis),
+            id_waker(millis, &woken),
+        );
+    }
+    // A deadline between two ticks is rounded up
+    wheel.insert(start + Duration::from_micros(7_500), id_waker(8, &woken));
+    deadlines.push(8);
+    deadlines.sort();
+
+    // Parks for as long as the wheel allows, like the executor does
+    let mut now = start;
+    let mut fired_at = HashMap::new();
+    while let Some(timeout) = wheel.next_timeout(now) {
+        now += timeout;
+        fire(wheel.expire(now));
+        for id in woken.lock().unwrap().drain(..) {
+            fired_at.insert(id, now - start);
+        }
+    }
+    assert_eq!(wheel.len(), 0);
+    for millis in deadlines {
+        assert_eq!(fired_at[&millis], Duration::from_millis(millis));
+    }
+}
+
+#[test]
+fn late_expire_fires_everything_due() {
+    let woken = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+    let mut wheel = TimerWheel::new(start);
+    for millis in [3, 100, 5000, 70_000] {
+        wheel.insert(
+            start + Duration::from_millis(millis),
+            id_waker(millis, &woken),
+        );
+    }
+    fire(wheel.expire(start + Duration::from_millis(6000)));
+    assert_eq!(*woken.lock().unwrap(), vec![3, 100, 5000]);
+    assert_eq!(wheel.len(), 1);
+    // Already due when inserted
+    wheel.insert(start, id_waker(0, &woken));
+    assert_eq!(
+        wheel.next_timeout(start + Duration::from_millis(6000)),
+        Some(Duration::ZERO)
+    );
+    fire(wheel.expire(start + Duration::from_millis(6000)));
+    assert_eq!(woken.lock().unwrap().last(), Some(&0));
+}